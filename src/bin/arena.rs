@@ -0,0 +1,169 @@
+//! Play many games between two strategies and report statistics.
+//!
+//! usage: arena STRATEGY STRATEGY [-n GAMES] [-b BOARD | -a] [-p]
+//!
+//! -n : number of games per board (default 10), colors alternate on each board
+//! -b : board name or file (default is the standard empty board)
+//! -a : play on all boards found in the boards search path
+//! -p : play games in parallel (one per core, engines may use several cores too)
+use blobwar::board::Board;
+use blobwar::configuration::{Configuration, GameOutcome, Verbosity};
+use blobwar::stats::Tally;
+use blobwar::strategy::registry::{available_strategies, parse_strategy};
+
+use std::env::args;
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+const USAGE: &str = "usage: arena STRATEGY STRATEGY [-n GAMES] [-b BOARD | -a] [-p]";
+
+/// Exit with given message and the usage.
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    exit(1)
+}
+
+/// Load boards to play on.
+fn boards(board: Option<String>, all: bool) -> Vec<(String, Board)> {
+    let mut boards = Vec::new();
+    if !all && board.is_none() {
+        boards.push(("standard".to_owned(), Board::default()));
+    }
+    let names: Vec<String> = if all {
+        Board::names()
+    } else {
+        board.into_iter().collect()
+    };
+    for name in names {
+        match Board::load(&name) {
+            Ok(board) => boards.push((name, board)),
+            Err(e) => fail(&format!("failed loading board '{}': {}", name, e)),
+        }
+    }
+    if boards.is_empty() {
+        fail("no board found in the boards search path");
+    }
+    boards
+}
+
+fn main() {
+    let mut specs = Vec::new();
+    let mut games = 10;
+    let mut board = None;
+    let mut all_boards = false;
+    let mut parallel = false;
+    let mut arguments = args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-n" => {
+                games = arguments
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n| n > 0)
+                    .unwrap_or_else(|| fail("-n needs a positive number of games"))
+            }
+            "-b" => board = Some(arguments.next().unwrap_or_else(|| fail("-b needs a board"))),
+            "-a" => all_boards = true,
+            "-p" => parallel = true,
+            "-h" | "--help" => fail(&format!(
+                "play games between two strategies, available strategies are:\n{}",
+                available_strategies()
+            )),
+            _ => specs.push(argument),
+        }
+    }
+    if all_boards && board.is_some() {
+        fail("-b and -a cannot be combined");
+    }
+    if specs.len() != 2 {
+        fail("two strategies are needed");
+    }
+    for spec in &specs {
+        if let Err(e) = parse_strategy(spec) {
+            fail(&e.to_string());
+        }
+    }
+    let boards = boards(board, all_boards);
+    let names = [
+        parse_strategy(&specs[0]).unwrap().to_string(),
+        parse_strategy(&specs[1]).unwrap().to_string(),
+    ];
+    println!("{} against {}", names[0], names[1]);
+
+    // game i is played on board i / games, first strategy is blue on odd games of each board
+    let play = |game: usize| -> (bool, GameOutcome) {
+        let (ref board_name, ref board) = boards[game / games];
+        let swapped = (game % games) % 2 == 1;
+        let (red, blue) = if swapped { (1, 0) } else { (0, 1) };
+        let mut configuration = Configuration::new(board);
+        let outcome = configuration.battle_with(
+            parse_strategy(&specs[red]).unwrap(),
+            parse_strategy(&specs[blue]).unwrap(),
+            Verbosity::Quiet,
+        );
+        println!(
+            "game {} on {}: {} (red) {} - {} {} (blue) in {} plies",
+            game + 1,
+            board_name,
+            names[red],
+            outcome.blobs[0],
+            outcome.blobs[1],
+            names[blue],
+            outcome.plies
+        );
+        (swapped, outcome)
+    };
+    let total = games * boards.len();
+    let outcomes: Vec<(bool, GameOutcome)> = if parallel {
+        // plain threads : rayon workers would start new games while waiting for searches
+        let next_game = AtomicUsize::new(0);
+        let outcomes = Mutex::new(Vec::new());
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+        thread::scope(|scope| {
+            for _ in 0..workers.min(total) {
+                scope.spawn(|| loop {
+                    let game = next_game.fetch_add(1, Ordering::Relaxed);
+                    if game >= total {
+                        break;
+                    }
+                    let outcome = play(game);
+                    outcomes.lock().unwrap().push(outcome);
+                });
+            }
+        });
+        outcomes.into_inner().unwrap()
+    } else {
+        (0..total).map(play).collect()
+    };
+
+    let mut tally = Tally::default();
+    let mut margins = 0i64;
+    let mut plies = 0;
+    for (swapped, outcome) in &outcomes {
+        let margin = if *swapped {
+            -outcome.margin()
+        } else {
+            outcome.margin()
+        };
+        tally.add(margin);
+        margins += i64::from(margin);
+        plies += outcome.plies;
+    }
+    let (elo, error) = tally.elo();
+    println!(
+        "{} wins, {} draws, {} losses for {} in {} games",
+        tally.wins,
+        tally.draws,
+        tally.losses,
+        names[0],
+        tally.games()
+    );
+    println!(
+        "average margin {:+.2}, average length {:.1} plies",
+        margins as f64 / total as f64,
+        plies as f64 / total as f64
+    );
+    println!("elo difference {:+.1} +/- {:.1}", elo, error);
+}
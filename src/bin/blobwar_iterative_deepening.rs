@@ -1,23 +1,61 @@
 use blobwar::board::Board;
 use blobwar::configuration::Configuration;
-use blobwar::strategy::{alpha_beta_anytime, min_max_anytime};
+use blobwar::strategy::{alpha_beta_anytime, evil, min_max_anytime, serve, IterativeStrategy};
 use std::env;
+use std::fmt::Display;
+use std::io;
+use std::process::exit;
 
+const USAGE: &str =
+    "usage: blobwar_iterative_deepening CONFIGURATION STRATEGY SHMEM | --worker STRATEGY";
+
+/// Report an invalid argument and exit.
+fn invalid<E: Display>(argument: &str, error: E) -> ! {
+    eprintln!("invalid {}: {}", argument, error);
+    exit(1)
+}
+
+/// Report a missing argument with the usage and exit.
+fn missing(argument: &str) -> ! {
+    eprintln!("missing {} argument\n{}", argument, USAGE);
+    exit(1)
+}
+
+/// Parse the strategy index argument.
+fn strategy(index: &str) -> IterativeStrategy {
+    index
+        .parse()
+        .ok()
+        .and_then(IterativeStrategy::from_index)
+        .unwrap_or_else(|| invalid("strategy number", index))
+}
+
+/// Usage : `blobwar_iterative_deepening CONFIGURATION STRATEGY SHMEM` to search until killed
+/// or `blobwar_iterative_deepening --worker STRATEGY` to serve searches on stdin.
 fn main() {
     let config_string = env::args()
         .nth(1)
-        .expect("missing argument to iterative deepening");
-    let strategy_index = env::args()
-        .nth(2)
-        .expect("missing argument to iterative deepening");
-    let board = Board::deserialize(&config_string);
-    let configuration = Configuration::deserialize(&config_string, &board);
-    match strategy_index
-        .parse::<usize>()
-        .expect("error parsing strategy integer")
-    {
-        0 => min_max_anytime(&configuration),
-        1 => alpha_beta_anytime(&configuration),
-        _ => panic!("invalid strategy number"),
+        .unwrap_or_else(|| missing("configuration"));
+    let strategy_index = env::args().nth(2).unwrap_or_else(|| missing("strategy"));
+    if config_string == "--worker" {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        if let Err(e) = serve(strategy(&strategy_index), stdin.lock(), stdout.lock()) {
+            eprintln!("worker failed: {}", e);
+            exit(1)
+        }
+        return;
+    }
+    let shmem = env::args()
+        .nth(3)
+        .unwrap_or_else(|| missing("shared memory name"));
+    let board =
+        Board::try_deserialize(&config_string).unwrap_or_else(|e| invalid("configuration", e));
+    let configuration = Configuration::try_deserialize(&config_string, &board)
+        .unwrap_or_else(|e| invalid("configuration", e));
+    match strategy(&strategy_index) {
+        IterativeStrategy::MinMax => min_max_anytime(&configuration, &shmem),
+        IterativeStrategy::AlphaBeta => alpha_beta_anytime(&configuration, &shmem),
+        IterativeStrategy::Evil => evil::alpha_beta_anytime(&configuration, &shmem),
     }
 }
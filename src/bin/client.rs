@@ -19,10 +19,17 @@ fn main() {
         .lines()
         .map(|r| r.expect("failed reading configuration from server"))
     {
-        let board = Board::deserialize(&line);
-        let game = Configuration::deserialize(&line, &board);
-        let next_move = strategy.compute_next_move(&game);
+        // on invalid configurations we report the error and pass
+        let next_move = Board::try_deserialize(&line)
+            .and_then(|board| {
+                Configuration::try_deserialize(&line, &board)
+                    .map(|game| strategy.compute_next_move(&game))
+            })
+            .unwrap_or_else(|e| {
+                eprintln!("invalid configuration received: {}", e);
+                None
+            });
         serde_json::to_writer(&mut sending, &next_move).expect("sending back movement failed");
-        sending.write(b"\n").expect("newline failed");
+        sending.write_all(b"\n").expect("newline failed");
     }
 }
@@ -1,12 +1,19 @@
 use blobwar::board::Board;
 use blobwar::configuration::Configuration;
-use blobwar::strategy::{IterativeDeepening, IterativeStrategy, NetworkPlayer};
+use blobwar::strategy::registry::parse_strategy;
+use blobwar::strategy::NetworkPlayer;
 
 use std::env::args;
 use std::net::TcpListener;
+use std::process::exit;
 
 fn main() {
     let map_name = args().nth(1).unwrap_or("standard".to_owned());
+    let spec = args().nth(2).unwrap_or("iterative:alphabeta".to_owned());
+    let player = parse_strategy(&spec).unwrap_or_else(|e| {
+        eprintln!("{}\nusage: server [MAP] [STRATEGY]", e);
+        exit(1)
+    });
     // we need two players
     let listener = TcpListener::bind("0.0.0.0:12345").expect("bind failed");
     let mut players = listener
@@ -16,8 +23,5 @@ fn main() {
     // create board and start game
     let board = Board::load(map_name).expect("failed loading map");
     let mut game = Configuration::new(&board);
-    game.battle(
-        IterativeDeepening::new(IterativeStrategy::AlphaBeta),
-        players.next().unwrap(),
-    );
+    game.battle(player, players.next().unwrap());
 }
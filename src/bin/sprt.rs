@@ -0,0 +1,174 @@
+//! Compare two strategies with a sequential probability ratio test.
+//! Games are played in pairs from the same random opening with swapped colors, until the
+//! log-likelihood ratio crosses one of the bounds.
+//!
+//! usage: sprt STRATEGY STRATEGY [-e ELO0 ELO1] [-r ALPHA BETA] [-b BOARD] [-o PLIES] [-m PAIRS]
+//!
+//! -e : Elo bounds for H0 and H1 (default 0 20), for the first strategy against the second
+//! -r : false positive and false negative rates (default 0.05 0.05)
+//! -b : board name or file (default is the standard empty board)
+//! -o : number of random plies in openings (default 4)
+//! -m : maximum number of pairs (default unlimited)
+//!
+//! exit status is 0 if H1 is accepted (the first strategy is stronger), 1 if it is rejected,
+//! 2 on usage errors and 3 if the maximum number of pairs was reached before a conclusion.
+use blobwar::board::Board;
+use blobwar::configuration::{Configuration, Verbosity};
+use blobwar::stats::{Pairs, Sprt, Tally, Verdict};
+use blobwar::strategy::registry::{available_strategies, parse_strategy};
+
+use std::collections::hash_map::RandomState;
+use std::env::args;
+use std::hash::{BuildHasher, Hasher};
+use std::process::exit;
+
+const USAGE: &str =
+    "usage: sprt STRATEGY STRATEGY [-e ELO0 ELO1] [-r ALPHA BETA] [-b BOARD] [-o PLIES] [-m PAIRS]";
+
+/// Exit with given message and the usage.
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    exit(2)
+}
+
+/// Parse next argument of given option.
+fn parameter<T: std::str::FromStr>(
+    arguments: &mut impl Iterator<Item = String>,
+    option: &str,
+) -> T {
+    arguments
+        .next()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| fail(&format!("invalid or missing value for {}", option)))
+}
+
+/// Play given number of random plies from the start, differently for each seed.
+fn opening<'a>(
+    board: &'a Board,
+    plies: usize,
+    random: &RandomState,
+    seed: u32,
+) -> Configuration<'a> {
+    let mut hasher = random.build_hasher();
+    hasher.write_u32(seed);
+    let mut bits = hasher.finish() | 1;
+    let mut configuration = Configuration::new(board);
+    for _ in 0..plies {
+        let movements: Vec<_> = configuration.movements().collect();
+        if movements.is_empty() {
+            break;
+        }
+        // xorshift
+        bits ^= bits << 13;
+        bits ^= bits >> 7;
+        bits ^= bits << 17;
+        configuration.apply_movement(&movements[(bits % movements.len() as u64) as usize]);
+    }
+    configuration
+}
+
+fn main() {
+    let mut specs = Vec::new();
+    let mut sprt = Sprt::default();
+    let mut board = None;
+    let mut plies = 4;
+    let mut max_pairs = None;
+    let mut arguments = args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-e" => {
+                sprt.elo0 = parameter(&mut arguments, "-e");
+                sprt.elo1 = parameter(&mut arguments, "-e");
+            }
+            "-r" => {
+                sprt.alpha = parameter(&mut arguments, "-r");
+                sprt.beta = parameter(&mut arguments, "-r");
+            }
+            "-b" => board = Some(parameter::<String>(&mut arguments, "-b")),
+            "-o" => plies = parameter(&mut arguments, "-o"),
+            "-m" => max_pairs = Some(parameter::<u32>(&mut arguments, "-m")),
+            "-h" | "--help" => fail(&format!(
+                "run a sprt between two strategies, available strategies are:\n{}",
+                available_strategies()
+            )),
+            _ => specs.push(argument),
+        }
+    }
+    if specs.len() != 2 {
+        fail("two strategies are needed");
+    }
+    if sprt.elo0 >= sprt.elo1 {
+        fail("elo0 must be lower than elo1");
+    }
+    let players: Vec<_> = specs
+        .iter()
+        .map(|spec| parse_strategy(spec).unwrap_or_else(|e| fail(&e.to_string())))
+        .collect();
+    let board = match board {
+        Some(name) => Board::load(&name)
+            .unwrap_or_else(|e| fail(&format!("failed loading board '{}': {}", name, e))),
+        None => Board::default(),
+    };
+    let (lower, upper) = sprt.bounds();
+    println!(
+        "{} against {}, H0: elo {}, H1: elo {}, llr bounds [{:.2}, {:.2}]",
+        players[0], players[1], sprt.elo0, sprt.elo1, lower, upper
+    );
+
+    let random = RandomState::new();
+    let mut pairs = Pairs::default();
+    let mut tally = Tally::default();
+    let verdict = loop {
+        if let Some(verdict) = sprt.verdict(&pairs) {
+            break Some(verdict);
+        }
+        if max_pairs.is_some_and(|max| pairs.pairs() >= max) {
+            break None;
+        }
+        let start = opening(&board, plies, &random, pairs.pairs());
+        let mut margins = [0; 2];
+        for (game, margin) in margins.iter_mut().enumerate() {
+            let (red, blue) = if game == 0 { (0, 1) } else { (1, 0) };
+            let mut configuration = start;
+            let outcome = configuration.battle_with(
+                parse_strategy(&specs[red]).unwrap(),
+                parse_strategy(&specs[blue]).unwrap(),
+                Verbosity::Quiet,
+            );
+            *margin = if red == 0 {
+                outcome.margin()
+            } else {
+                -outcome.margin()
+            };
+            tally.add(*margin);
+        }
+        pairs.add(margins[0], margins[1]);
+        println!(
+            "pair {}: {:+} {:+}, {} wins {} draws {} losses, llr {:.2}",
+            pairs.pairs(),
+            margins[0],
+            margins[1],
+            tally.wins,
+            tally.draws,
+            tally.losses,
+            sprt.llr(&pairs)
+        );
+    };
+
+    let (elo, error) = tally.elo();
+    println!("elo difference {:+.1} +/- {:.1}", elo, error);
+    match verdict {
+        Some(Verdict::Accepted) => {
+            println!("H1 accepted: {} is stronger", players[0]);
+            exit(0)
+        }
+        Some(Verdict::Rejected) => {
+            println!("H1 rejected: {} is not stronger", players[0]);
+            exit(1)
+        }
+        None => {
+            println!("no conclusion after {} pairs", pairs.pairs());
+            exit(3)
+        }
+    }
+}
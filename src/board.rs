@@ -1,12 +1,92 @@
-//! Board related features. Provides the `Board` structure storing holes.
+//! Board related features. Provides the `Board` structure storing holes and starting blobs.
+//!
+//! Board files have one line of 8 cells per row : `x` for holes, `r` and `b` for starting red
+//! and blue blobs and `O` or `.` for empty cells. Red and blue start in the corners if no
+//! blob is given. A `first: blue` line lets blue play first. Empty lines and lines starting
+//! with `#` are ignored.
 use std::cmp::{max, min};
-use std::fs::File;
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
 use std::io;
-use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use super::configuration::{parse_serialized, DeserializeError};
 use super::positions::{BoardPosition, Position, Positions};
 
+/// Environment variable listing directories where boards are looked up.
+pub const BOARDS_VARIABLE: &str = "BLOBWAR_BOARDS";
+
+#[derive(Debug)]
+/// Everything which can go wrong when loading a board.
+pub enum BoardError {
+    /// No board file at any of these paths.
+    NotFound(Vec<PathBuf>),
+    /// Failed reading given file.
+    Io(PathBuf, io::Error),
+    /// Invalid cell at given line and column (both starting at 1).
+    UnknownCharacter {
+        /// Line of the cell.
+        line: usize,
+        /// Column of the cell.
+        column: usize,
+        /// Cell content.
+        character: char,
+    },
+    /// Row at given line does not have 8 cells.
+    WrongWidth {
+        /// Line of the row.
+        line: usize,
+        /// Number of cells.
+        width: usize,
+    },
+    /// There are not 8 rows.
+    WrongHeight(usize),
+    /// Invalid `first:` line.
+    InvalidFirstPlayer(usize),
+    /// Starting blobs are given for one player only.
+    MissingBlobs,
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardError::NotFound(paths) => {
+                write!(f, "board not found, tried")?;
+                for path in paths {
+                    write!(f, " {}", path.display())?;
+                }
+                Ok(())
+            }
+            BoardError::Io(path, error) => {
+                write!(f, "failed reading {}: {}", path.display(), error)
+            }
+            BoardError::UnknownCharacter {
+                line,
+                column,
+                character,
+            } => write!(
+                f,
+                "line {}, column {}: unknown cell '{}' (expected x, r, b, O or .)",
+                line, column, character
+            ),
+            BoardError::WrongWidth { line, width } => {
+                write!(f, "line {}: {} cells instead of 8", line, width)
+            }
+            BoardError::WrongHeight(height) => write!(f, "{} rows instead of 8", height),
+            BoardError::InvalidFirstPlayer(line) => {
+                write!(f, "line {}: first player should be red or blue", line)
+            }
+            BoardError::MissingBlobs => write!(f, "starting blobs are needed for both players"),
+        }
+    }
+}
+
+impl Error for BoardError {}
+
 /// Board representation.
 pub struct Board {
     /// `Positions` of the holes.
@@ -18,6 +98,10 @@ pub struct Board {
     pub individual_neighbours: [Vec<Vec<Position>>; 2],
     /// Associate to each `Position` the `Positions` of all its neighbours.
     pub neighbours: Vec<Positions>,
+    /// Starting `Positions` of red and blue blobs.
+    pub start: [Positions; 2],
+    /// Player playing first (false for red, true for blue).
+    pub first_player: bool,
 }
 
 impl Default for Board {
@@ -28,31 +112,57 @@ impl Default for Board {
 
 impl Board {
     /// Compute new `Board` structure from given holes.
+    /// Red starts first in the top left and bottom right corners, blue in the other ones.
     pub fn new(holes: Positions) -> Self {
         let mut board = Board {
             holes,
             individual_neighbours: [Vec::new(), Vec::new()],
             neighbours: Vec::new(),
+            start: [
+                Positions::single(0).union_with(Positions::single(63)),
+                Positions::single(7).union_with(Positions::single(56)),
+            ],
+            first_player: false,
         };
         board.fill_individual_neighbours();
         board.fill_neighbours();
         board
     }
 
-    /// Load a `Board` from given file.
-    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let file = File::open(Path::new("boards").join(path))?;
-        let mut bit = 1u64;
-        let mut positions_code = 0;
-        for line in io::BufReader::new(file).lines() {
-            for character in line?.chars() {
-                if character == 'x' {
-                    positions_code |= bit;
-                }
-                bit <<= 1;
-            }
-        }
-        Ok(Board::new(Positions(positions_code)))
+    /// Directories where boards are looked up by name : the ones listed in the
+    /// `BLOBWAR_BOARDS` environment variable, then `boards` in the current directory and in
+    /// the sources.
+    pub fn search_path() -> Vec<PathBuf> {
+        search_path_from(env::var_os(BOARDS_VARIABLE))
+    }
+
+    /// Find the file of given board. Bare names are looked up in the `search_path` and then
+    /// in the current directory, other paths are used as they are.
+    pub fn find<P: AsRef<Path>>(path: P) -> Result<PathBuf, BoardError> {
+        find_in(path.as_ref(), &Board::search_path())
+    }
+
+    /// Names of all boards found in the `search_path` (each name only once), sorted.
+    pub fn names() -> Vec<String> {
+        let mut names: Vec<String> = Board::search_path()
+            .iter()
+            .filter_map(|directory| fs::read_dir(directory).ok())
+            .flatten()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_file())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Load a `Board` from given file (see `find` for the lookup of bare names).
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, BoardError> {
+        let path = Board::find(path)?;
+        fs::read_to_string(&path)
+            .map_err(|error| BoardError::Io(path, error))?
+            .parse()
     }
 
     /// Pre-compute valid neighbours for each position.
@@ -92,17 +202,242 @@ impl Board {
     }
 
     /// Deserialize serialized `Configuration` into `Board`.
+    /// Panics if the string is invalid, see `try_deserialize`.
     pub fn deserialize(string: &str) -> Self {
-        let mut holes = 0;
-        let mut bit = 1u64;
-        for code in string.chars().skip(1) {
-            match code {
-                'h' => holes |= bit,
-                ' ' | 'r' | 'b' => {}
-                _ => panic!("invalid cell content"),
+        Board::try_deserialize(string).unwrap_or_else(|e| panic!("invalid configuration: {}", e))
+    }
+
+    /// Deserialize serialized `Configuration` into `Board`.
+    pub fn try_deserialize(string: &str) -> Result<Self, DeserializeError> {
+        Ok(Board::new(Positions(parse_serialized(string)?.holes)))
+    }
+}
+
+/// Search path given the value of the `BLOBWAR_BOARDS` variable.
+fn search_path_from(variable: Option<OsString>) -> Vec<PathBuf> {
+    let mut directories: Vec<PathBuf> = variable
+        .map(|paths| env::split_paths(&paths).collect())
+        .unwrap_or_default();
+    directories.push(PathBuf::from("boards"));
+    directories.push(Path::new(env!("CARGO_MANIFEST_DIR")).join("boards"));
+    directories
+}
+
+/// Find the file of given board, looking up bare names in given directories.
+fn find_in(path: &Path, directories: &[PathBuf]) -> Result<PathBuf, BoardError> {
+    let mut candidates = Vec::new();
+    if path.components().count() == 1 && !path.is_absolute() {
+        candidates.extend(directories.iter().map(|directory| directory.join(path)));
+    }
+    candidates.push(path.to_owned());
+    match candidates.iter().find(|candidate| candidate.is_file()) {
+        Some(found) => Ok(found.clone()),
+        None => Err(BoardError::NotFound(candidates)),
+    }
+}
+
+impl FromStr for Board {
+    type Err = BoardError;
+    /// Read a board in the file format.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut holes = 0u64;
+        let mut blobs = [0u64; 2];
+        let mut first_player = false;
+        let mut rows = 0;
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
             }
-            bit <<= 1;
+            if let Some(player) = line.strip_prefix("first:") {
+                first_player = match player.trim() {
+                    "red" => false,
+                    "blue" => true,
+                    _ => return Err(BoardError::InvalidFirstPlayer(line_number)),
+                };
+                continue;
+            }
+            let width = line.chars().count();
+            if width != 8 {
+                return Err(BoardError::WrongWidth {
+                    line: line_number,
+                    width,
+                });
+            }
+            for (column, character) in line.chars().enumerate() {
+                let bit = 1u64.checked_shl((rows * 8 + column) as u32).unwrap_or(0);
+                match character {
+                    'x' => holes |= bit,
+                    'r' => blobs[0] |= bit,
+                    'b' => blobs[1] |= bit,
+                    'O' | '.' => {}
+                    _ => {
+                        return Err(BoardError::UnknownCharacter {
+                            line: line_number,
+                            column: column + 1,
+                            character,
+                        })
+                    }
+                }
+            }
+            rows += 1;
+        }
+        if rows != 8 {
+            return Err(BoardError::WrongHeight(rows));
+        }
+        let mut board = Board::new(Positions(holes));
+        match blobs {
+            [0, 0] => {}
+            [0, _] | [_, 0] => return Err(BoardError::MissingBlobs),
+            [red, blue] => board.start = [Positions(red), Positions(blue)],
+        }
+        board.first_player = first_player;
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::Configuration;
+
+    /// Standard board in the file format, with given replacements.
+    fn board_text(replacements: &[(&str, &str)]) -> String {
+        let text = "rOOOOOOb\nOOOOOOOO\nOOOOOOOO\nOOOxxOOO\n\
+                    OOOxxOOO\nOOOOOOOO\nOOOOOOOO\nbOOOOOOr\n";
+        replacements
+            .iter()
+            .fold(text.to_owned(), |text, (from, to)| {
+                text.replacen(from, to, 1)
+            })
+    }
+
+    /// New empty directory for the test of given name.
+    fn scratch(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("blobwar-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn read_boards() {
+        let board: Board = board_text(&[("OOOOOOOO\n", "# comment\n\nOOOOOOOO\n")])
+            .parse()
+            .unwrap();
+        assert_eq!(board.holes.0, (0b11 << 27) | (0b11 << 35));
+        assert_eq!(board.start[0].0, Board::default().start[0].0);
+        assert_eq!(board.start[1].0, Board::default().start[1].0);
+        assert!(!board.first_player);
+        let board: Board = (board_text(&[("r", "."), ("b", "."), ("b", "."), ("r", ".")])
+            + "first: blue\n")
+            .parse()
+            .unwrap();
+        assert_eq!(board.start[0].0, Board::default().start[0].0);
+        assert!(board.first_player);
+    }
+
+    #[test]
+    fn board_errors() {
+        let parse = |text: String| text.parse::<Board>().err().unwrap();
+        assert!(matches!(
+            parse(board_text(&[("xx", "xy")])),
+            BoardError::UnknownCharacter {
+                line: 4,
+                column: 5,
+                character: 'y'
+            }
+        ));
+        assert!(matches!(
+            parse(board_text(&[("OOOOOOOO", "OOOOOOO")])),
+            BoardError::WrongWidth { line: 2, width: 7 }
+        ));
+        assert!(matches!(
+            parse(board_text(&[("OOOOOOOO\n", "")])),
+            BoardError::WrongHeight(7)
+        ));
+        assert!(matches!(
+            parse(board_text(&[]) + "OOOOOOOO\n"),
+            BoardError::WrongHeight(9)
+        ));
+        assert!(matches!(
+            parse(board_text(&[]) + "first: green\n"),
+            BoardError::InvalidFirstPlayer(9)
+        ));
+        assert!(matches!(
+            parse(board_text(&[("b", "O"), ("b", "O")])),
+            BoardError::MissingBlobs
+        ));
+        let directory = scratch("board-errors");
+        let binary = directory.join("binary");
+        fs::write(&binary, [0xff, 0xfe, 0xfd]).unwrap();
+        assert!(matches!(Board::load(&binary), Err(BoardError::Io(path, _)) if path == binary));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn serialized_board_errors() {
+        let serialized = Configuration::new(&Board::default()).serialize();
+        assert!(Board::try_deserialize(&serialized).is_ok());
+        assert_eq!(
+            Board::try_deserialize(&serialized[1..]).err(),
+            Some(DeserializeError::BadLength(64))
+        );
+        assert_eq!(
+            Board::try_deserialize(&serialized.replacen('0', "2", 1)).err(),
+            Some(DeserializeError::BadPlayerCode('2'))
+        );
+        assert_eq!(
+            Board::try_deserialize(&serialized.replacen(' ', "x", 1)).err(),
+            Some(DeserializeError::InvalidCharacter {
+                index: 2,
+                character: 'x'
+            })
+        );
+    }
+
+    #[test]
+    fn search_path_order() {
+        let directory = scratch("search-path");
+        let (first, second) = (directory.join("first"), directory.join("second"));
+        let variable = env::join_paths([&first, &second]).unwrap();
+        let path = search_path_from(Some(variable));
+        assert_eq!(
+            path,
+            vec![
+                first.clone(),
+                second.clone(),
+                PathBuf::from("boards"),
+                Path::new(env!("CARGO_MANIFEST_DIR")).join("boards"),
+            ]
+        );
+        assert_eq!(search_path_from(None), path[2..].to_vec());
+
+        let name = Path::new("blobwar-test-board");
+        for found in &[&second, &first] {
+            fs::create_dir_all(found).unwrap();
+            fs::write(found.join(name), board_text(&[])).unwrap();
+            assert_eq!(find_in(name, &path).unwrap(), found.join(name));
+        }
+        fs::remove_file(first.join(name)).unwrap();
+        assert_eq!(find_in(name, &path).unwrap(), second.join(name));
+        fs::remove_file(second.join(name)).unwrap();
+        match find_in(name, &path) {
+            Err(BoardError::NotFound(candidates)) => assert_eq!(
+                candidates,
+                path.iter()
+                    .map(|directory| directory.join(name))
+                    .chain(Some(name.to_owned()))
+                    .collect::<Vec<_>>()
+            ),
+            other => panic!("unexpected {:?}", other),
         }
-        Board::new(Positions(holes))
+        // paths with directories are not looked up
+        let file = second.join("explicit");
+        fs::write(&file, board_text(&[])).unwrap();
+        assert_eq!(find_in(&file, &path).unwrap(), file);
+        assert!(Board::load(&file).is_ok());
+        fs::remove_dir_all(&directory).unwrap();
     }
 }
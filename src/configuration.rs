@@ -1,10 +1,18 @@
 //! Provide a `Configuration` for storing game state ; a `Movement` for storing moves to play.
 use super::board::Board;
+use super::game::Game;
 use super::positions::{BoardPosition, Position, Positions};
+use super::record::{GameRecord, RecordedMove};
 use super::strategy::Strategy;
+use super::zobrist;
 use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::error::Error;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::iter::once;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 use term;
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
@@ -16,6 +24,218 @@ pub enum Movement {
     Jump(u8, u8),
 }
 
+/// Write given cell in the notation : a file from `a` (left) to `h` followed by a rank from
+/// `1` (bottom) to `8`, like in Ataxx.
+fn write_cell(f: &mut fmt::Formatter, position: Position) -> fmt::Result {
+    let (x, y) = position.to_2d();
+    write!(f, "{}{}", (b'a' + x) as char, 8 - y)
+}
+
+/// Read a cell written in the notation.
+fn parse_cell(file: u8, rank: u8) -> Option<Position> {
+    match (file, rank) {
+        (b'a'..=b'h', b'1'..=b'8') => Some(Position::from_2d(file - b'a', b'8' - rank)),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Movement {
+    /// Ataxx notation : `c3` duplicates to c3 and `a1c3` jumps from a1 to c3.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Movement::Duplicate(destination) => write_cell(f, destination),
+            Movement::Jump(source, destination) => {
+                write_cell(f, source)?;
+                write_cell(f, destination)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Text which is not a movement in the notation.
+pub struct ParseMovementError(String);
+
+impl fmt::Display for ParseMovementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid movement '{}' (expected a cell like c3 or two cells like a1c3)",
+            self.0
+        )
+    }
+}
+
+impl Error for ParseMovementError {}
+
+impl FromStr for Movement {
+    type Err = ParseMovementError;
+    /// Read the Ataxx notation. Two neighbouring cells also denote a duplication.
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let error = || ParseMovementError(string.to_owned());
+        match *string.trim().to_ascii_lowercase().as_bytes() {
+            [file, rank] => parse_cell(file, rank)
+                .map(Movement::Duplicate)
+                .ok_or_else(error),
+            [source_file, source_rank, file, rank] => {
+                let source = parse_cell(source_file, source_rank).ok_or_else(error)?;
+                let destination = parse_cell(file, rank).ok_or_else(error)?;
+                match source.distance_to(destination) {
+                    1 => Ok(Movement::Duplicate(destination)),
+                    2 => Ok(Movement::Jump(source, destination)),
+                    _ => Err(error()),
+                }
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Everything which can be wrong in a serialized `Configuration`.
+pub enum DeserializeError {
+    /// The string does not have one player code and 64 cells (its length is given).
+    BadLength(usize),
+    /// The first character is not `0` (red to play) or `1` (blue to play).
+    BadPlayerCode(char),
+    /// Cell at given index of the string is not `r`, `b`, `h` or a space.
+    InvalidCharacter {
+        /// Index in the string (the player code is at index 0).
+        index: usize,
+        /// Cell content.
+        character: char,
+    },
+    /// Blob at given index of the string is on a hole of the board.
+    Overlapping(usize),
+    /// Cell at given index of the string is a hole and not on the board, or the reverse.
+    HoleMismatch(usize),
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeserializeError::BadLength(length) => {
+                write!(f, "{} characters instead of 65", length)
+            }
+            DeserializeError::BadPlayerCode(code) => {
+                write!(f, "invalid player code '{}' (expected 0 or 1)", code)
+            }
+            DeserializeError::InvalidCharacter { index, character } => write!(
+                f,
+                "invalid cell '{}' at index {} (expected r, b, h or space)",
+                character, index
+            ),
+            DeserializeError::Overlapping(index) => {
+                write!(f, "blob on a hole at index {}", index)
+            }
+            DeserializeError::HoleMismatch(index) => {
+                write!(f, "hole at index {} differs from the board", index)
+            }
+        }
+    }
+}
+
+impl Error for DeserializeError {}
+
+/// Content of a serialized `Configuration`.
+pub(crate) struct SerializedCells {
+    pub current_player: bool,
+    pub holes: u64,
+    pub blobs: [u64; 2],
+}
+
+/// Check and decode a serialized `Configuration`.
+pub(crate) fn parse_serialized(string: &str) -> Result<SerializedCells, DeserializeError> {
+    let length = string.chars().count();
+    if length != 65 {
+        return Err(DeserializeError::BadLength(length));
+    }
+    let mut chars = string.chars();
+    let current_player = match chars.next() {
+        Some('0') => false,
+        Some('1') => true,
+        Some(code) => return Err(DeserializeError::BadPlayerCode(code)),
+        None => return Err(DeserializeError::BadLength(0)),
+    };
+    let mut cells = SerializedCells {
+        current_player,
+        holes: 0,
+        blobs: [0; 2],
+    };
+    for (cell, character) in chars.enumerate() {
+        let bit = 1u64 << cell;
+        match character {
+            'h' => cells.holes |= bit,
+            'r' => cells.blobs[0] |= bit,
+            'b' => cells.blobs[1] |= bit,
+            ' ' => {}
+            _ => {
+                return Err(DeserializeError::InvalidCharacter {
+                    index: cell + 1,
+                    character,
+                })
+            }
+        }
+    }
+    Ok(cells)
+}
+
+impl<'a> TryFrom<(&str, &'a Board)> for Configuration<'a> {
+    type Error = DeserializeError;
+    /// Deserialize a `Configuration` on given `Board`, see `Configuration::try_deserialize`.
+    fn try_from((string, board): (&str, &'a Board)) -> Result<Self, Self::Error> {
+        Configuration::try_deserialize(string, board)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// How much `battle` prints.
+pub enum Verbosity {
+    /// Display nothing.
+    Quiet,
+    /// Display board before each turn and final result.
+    Normal,
+    /// Also display search reports of players after each of their moves.
+    Search,
+}
+
+#[derive(Clone, Debug)]
+/// Everything that happened during a `battle`.
+pub struct GameOutcome {
+    /// Winning player (false for red, true for blue), None for a draw.
+    pub winner: Option<bool>,
+    /// Final number of blobs of red and blue.
+    pub blobs: [i8; 2],
+    /// Number of turns played, passes included.
+    pub plies: usize,
+    /// Number of turns skipped because no move was returned.
+    pub passes: usize,
+    /// Movement of each turn (None for passes).
+    pub movements: Vec<Option<Movement>>,
+    /// Player of the first turn (false for red, true for blue).
+    pub first_player: bool,
+    /// Time taken by the player of each turn to decide.
+    pub thinking_times: Vec<Duration>,
+    /// Record of the game, with scores of searching players.
+    pub record: GameRecord,
+}
+
+impl GameOutcome {
+    /// Number of red blobs minus number of blue blobs.
+    pub fn margin(&self) -> i8 {
+        self.blobs[0] - self.blobs[1]
+    }
+
+    /// Total thinking time of given player (false for red, true for blue).
+    pub fn thinking_time(&self, player: bool) -> Duration {
+        self.thinking_times
+            .iter()
+            .skip((player != self.first_player) as usize)
+            .step_by(2)
+            .sum()
+    }
+}
+
 #[derive(Copy, Clone)]
 /// Game state. We know, who should play, what is the board and where every blob is located.
 pub struct Configuration<'a> {
@@ -23,18 +243,19 @@ pub struct Configuration<'a> {
     board: &'a Board,
     /// Who should play now.
     pub current_player: bool,
+    /// Zobrist hash of all the above, maintained incrementally.
+    zobrist: u64,
 }
 
 impl<'a> Configuration<'a> {
-    /// Create an initial game configuration out of given `Board`.
+    /// Create an initial game configuration out of given `Board` and its starting blobs.
     pub fn new(board: &'a Board) -> Self {
+        let blobs = board.start;
         Configuration {
-            blobs: [
-                Positions::single(0).union_with(Positions::single(63)),
-                Positions::single(7).union_with(Positions::single(56)),
-            ],
+            blobs,
             board,
-            current_player: false,
+            current_player: board.first_player,
+            zobrist: zobrist::hash(&blobs, board.holes, board.first_player),
         }
     }
 
@@ -45,6 +266,7 @@ impl<'a> Configuration<'a> {
         let destination = match *movement {
             Movement::Jump(source, destination) => {
                 self.blobs[me].remove(Positions::single(source));
+                self.zobrist ^= zobrist::CELL_KEYS[me][source as usize];
                 destination
             }
             Movement::Duplicate(destination) => destination,
@@ -56,6 +278,11 @@ impl<'a> Configuration<'a> {
         self.blobs[me].add(Positions::single(destination));
         self.blobs[him].remove(changing_blobs);
         self.current_player = !self.current_player;
+        self.zobrist ^= zobrist::positions_key(changing_blobs, me)
+            ^ zobrist::positions_key(changing_blobs, him)
+            ^ zobrist::CELL_KEYS[me][destination as usize]
+            ^ zobrist::PLAYER_KEY;
+        self.debug_check_zobrist();
     }
 
     /// Create a new `Configuration` by playing given `Movement` on self.
@@ -69,6 +296,8 @@ impl<'a> Configuration<'a> {
     pub fn skip_play(&self) -> Self {
         let mut new_configuration = *self;
         new_configuration.current_player = !new_configuration.current_player;
+        new_configuration.zobrist ^= zobrist::PLAYER_KEY;
+        new_configuration.debug_check_zobrist();
         new_configuration
     }
 
@@ -79,11 +308,34 @@ impl<'a> Configuration<'a> {
 
     /// Iterate on all `Position`(s) of empty cells.
     pub fn empty_cells(&self) -> impl Iterator<Item = Position> {
+        self.empty().positions()
+    }
+
+    /// `Positions` of empty cells.
+    pub(crate) fn empty(&self) -> Positions {
         self.blobs[0]
             .union_with(self.blobs[1])
             .union_with(self.board.holes)
             .invert()
-            .positions()
+    }
+
+    /// `Positions` of the blobs of given player.
+    pub(crate) fn blobs(&self, player: bool) -> Positions {
+        self.blobs[player as usize]
+    }
+
+    /// Return the 64 bits zobrist hash of the configuration (blobs, holes and current player).
+    pub fn zobrist(&self) -> u64 {
+        self.zobrist
+    }
+
+    /// Check in debug builds that the incremental hash did not drift from the real one.
+    fn debug_check_zobrist(&self) {
+        debug_assert_eq!(
+            self.zobrist,
+            zobrist::hash(&self.blobs, self.board.holes, self.current_player),
+            "incremental zobrist hash differs from recomputed one"
+        );
     }
 
     /// Return the configuration value (#other_player - #current_player)
@@ -122,39 +374,129 @@ impl<'a> Configuration<'a> {
     }
 
     /// Play a match between the given players starting from current `Configuration`.
-    pub fn battle<T: Strategy, U: Strategy>(&mut self, mut player_one: T, mut player_two: U) {
-        while !self.game_over() {
-            println!(
-                "{} player's turn (he is losing by {} before playing)",
-                ["red", "blue"][self.current_player as usize],
-                self.value()
-            );
-            println!("{}", self);
-            let play_attempt = if self.current_player {
-                player_two.compute_next_move(self)
+    pub fn battle<T: Strategy, U: Strategy>(
+        &mut self,
+        player_one: T,
+        player_two: U,
+    ) -> GameOutcome {
+        self.battle_with(player_one, player_two, Verbosity::Normal)
+    }
+
+    /// Play a match between the given players starting from current `Configuration`,
+    /// displaying more or less information.
+    /// The game also stops if both players pass in a row since nothing can change anymore.
+    pub fn battle_with<T: Strategy, U: Strategy>(
+        &mut self,
+        mut player_one: T,
+        mut player_two: U,
+        verbosity: Verbosity,
+    ) -> GameOutcome {
+        let first_player = self.current_player;
+        let mut game = Game::new(*self);
+        let mut thinking_times = Vec::new();
+        let mut record = GameRecord::new(self, [player_one.to_string(), player_two.to_string()]);
+        loop {
+            let state = *game.configuration();
+            let passes_in_a_row = game
+                .history()
+                .iter()
+                .rev()
+                .take_while(|movement| movement.is_none())
+                .count();
+            if state.game_over() || passes_in_a_row >= 2 {
+                break;
+            }
+            if verbosity != Verbosity::Quiet {
+                println!(
+                    "{} player's turn (he is losing by {} before playing)",
+                    ["red", "blue"][state.current_player as usize],
+                    state.value()
+                );
+                println!("{}", state);
+            }
+            let player: &mut dyn Strategy = if state.current_player {
+                &mut player_two
             } else {
-                player_one.compute_next_move(self)
+                &mut player_one
             };
-            if let Some(ref next_move) = play_attempt {
-                assert!(self.check_move(next_move));
-                self.apply_movement(next_move);
-            } else {
-                self.current_player = !self.current_player;
+            let start = Instant::now();
+            let play_attempt = player.compute_next_move(&state);
+            let thinking_time = start.elapsed();
+            if player.wants_undo() {
+                // back to the previous turn of this player
+                match game.previous_turn() {
+                    Some(ply) => {
+                        game.jump(ply);
+                        thinking_times.truncate(ply);
+                        record.moves.truncate(ply);
+                    }
+                    None if verbosity != Verbosity::Quiet => println!("nothing to undo"),
+                    None => {}
+                }
+                continue;
             }
+            thinking_times.push(thinking_time);
+            let info = player.search_info();
+            if verbosity == Verbosity::Search {
+                if let Some(info) = info {
+                    println!("{}", info);
+                }
+            }
+            // illegal movements are reported and count as passes
+            let play_attempt = play_attempt.filter(|next_move| {
+                let legal = state.check_move(next_move);
+                if !legal {
+                    eprintln!(
+                        "{} played illegal movement {}, turn skipped",
+                        player, next_move
+                    );
+                }
+                legal
+            });
+            game.play(play_attempt);
+            record.moves.push(RecordedMove {
+                movement: play_attempt,
+                score: info.map(|info| info.score),
+                comment: None,
+            });
         }
+        *self = *game.configuration();
+        let movements = game.history().to_vec();
+        let passes = movements
+            .iter()
+            .filter(|movement| movement.is_none())
+            .count();
 
-        let value = self.blobs[0].len() - self.blobs[1].len();
-        match value {
-            x if x > 0 => println!("RED ({}) wins over BLUE ({})!", player_one, player_two),
-            x if x < 0 => println!("BLUE ({}) wins over RED ({})!", player_two, player_one),
-            _ => println!("DRAW!"),
+        let blobs = [self.blobs[0].len(), self.blobs[1].len()];
+        let value = blobs[0] - blobs[1];
+        record.result = Some(blobs);
+        if verbosity != Verbosity::Quiet {
+            match value {
+                x if x > 0 => println!("RED ({}) wins over BLUE ({})!", player_one, player_two),
+                x if x < 0 => println!("BLUE ({}) wins over RED ({})!", player_two, player_one),
+                _ => println!("DRAW!"),
+            }
+            println!("{}", self);
+            println!("GAME OVER (red value of {})", value);
+        }
+        GameOutcome {
+            winner: match value {
+                x if x > 0 => Some(false),
+                x if x < 0 => Some(true),
+                _ => None,
+            },
+            blobs,
+            plies: movements.len(),
+            passes,
+            movements,
+            first_player,
+            thinking_times,
+            record,
         }
-        println!("{}", self);
-        println!("GAME OVER (red value of {})", value);
     }
 
     /// Return true if no empty space remains or someone died.
-    fn game_over(&self) -> bool {
+    pub(crate) fn game_over(&self) -> bool {
         self.blobs[0].is_empty()
             || self.blobs[1].is_empty()
             || self.blobs[0]
@@ -214,40 +556,60 @@ impl<'a> Configuration<'a> {
 
     /// Deserialize given `String` into a `Configuration`. You need to deserialize the `Board`
     /// first.
+    /// Panics if the string is invalid, see `try_deserialize`.
     pub fn deserialize(string: &str, board: &'a Board) -> Self {
-        let mut chars = string.chars();
-        let current_player;
-        if let Some(player_char) = chars.next() {
-            current_player = match player_char {
-                '1' => true,
-                '0' => false,
-                _ => panic!("invalid player code"),
-            }
-        } else {
-            panic!("missing player code");
+        Configuration::try_deserialize(string, board)
+            .unwrap_or_else(|e| panic!("invalid configuration: {}", e))
+    }
+
+    /// Deserialize given `String` into a `Configuration` on given `Board`.
+    /// Blobs on holes of the board and holes differing from the board's are refused.
+    pub fn try_deserialize(string: &str, board: &'a Board) -> Result<Self, DeserializeError> {
+        let cells = parse_serialized(string)?;
+        let overlap = (cells.blobs[0] | cells.blobs[1]) & board.holes.0;
+        if overlap != 0 {
+            return Err(DeserializeError::Overlapping(
+                overlap.trailing_zeros() as usize + 1,
+            ));
         }
-        let mut blobs = [0; 2];
-        let mut bit = 1u64;
-        for code in chars {
-            match code {
-                'r' => blobs[0] |= bit,
-                'b' => blobs[1] |= bit,
-                ' ' | 'h' => {}
-                _ => panic!("invalid cell content"),
-            }
-            bit <<= 1;
+        let mismatch = cells.holes ^ board.holes.0;
+        if mismatch != 0 {
+            return Err(DeserializeError::HoleMismatch(
+                mismatch.trailing_zeros() as usize + 1,
+            ));
         }
-        Configuration {
+        let blobs = [Positions(cells.blobs[0]), Positions(cells.blobs[1])];
+        Ok(Configuration {
             board,
-            blobs: [Positions(blobs[0]), Positions(blobs[1])],
-            current_player,
-        }
+            blobs,
+            current_player: cells.current_player,
+            zobrist: zobrist::hash(&blobs, board.holes, cells.current_player),
+        })
+    }
+}
+
+impl<'a> Hash for Configuration<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.zobrist)
+    }
+}
+
+impl<'a> PartialEq for Configuration<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        // hashes differ most of the time so we compare them first
+        self.zobrist == other.zobrist
+            && self.current_player == other.current_player
+            && *self.blobs[0] == *other.blobs[0]
+            && *self.blobs[1] == *other.blobs[1]
+            && *self.board.holes == *other.board.holes
     }
 }
 
+impl<'a> Eq for Configuration<'a> {}
+
 impl<'a> fmt::Display for Configuration<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "\n  01234567 \n")?;
+        write!(f, "\n  abcdefgh \n")?;
         write!(f, " +--------+\n")?;
         for (index, (hole, (red, blue))) in self
             .board
@@ -257,7 +619,7 @@ impl<'a> fmt::Display for Configuration<'a> {
             .enumerate()
         {
             if index % 8 == 0 {
-                write!(f, "{}|", index / 8)?;
+                write!(f, "{}|", 8 - index / 8)?;
             }
             let mut t = term::stdout().unwrap();
             match (hole, red, blue) {
@@ -283,3 +645,129 @@ impl<'a> fmt::Display for Configuration<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recomputed(state: &Configuration) -> u64 {
+        zobrist::hash(&state.blobs, state.board.holes, state.current_player)
+    }
+
+    #[test]
+    fn movement_notation() {
+        assert_eq!(Movement::Duplicate(0).to_string(), "a8");
+        assert_eq!(Movement::Duplicate(63).to_string(), "h1");
+        assert_eq!(Movement::Jump(56, 42).to_string(), "a1c3");
+        assert_eq!("a8".parse(), Ok(Movement::Duplicate(0)));
+        assert_eq!(" A1C3 ".parse(), Ok(Movement::Jump(56, 42)));
+        for source in 0..64u8 {
+            let movement = Movement::Duplicate(source);
+            assert_eq!(movement.to_string().parse(), Ok(movement));
+            for destination in 0..64u8 {
+                let movement = Movement::Jump(source, destination);
+                if source.distance_to(destination) == 2 {
+                    assert_eq!(movement.to_string().parse(), Ok(movement));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn adjacent_cells_are_a_duplication() {
+        assert_eq!("a1b2".parse(), Ok(Movement::Duplicate(49)));
+        assert_eq!("c3c4".parse::<Movement>(), "c4".parse());
+        assert_eq!("h8g8".parse(), Ok(Movement::Duplicate(6)));
+    }
+
+    #[test]
+    fn invalid_movements() {
+        for text in &[
+            "", "a", "a9", "i1", "a0", "11", "aa", "a1c", "a1c3e5", "a1a1", "a1a4", "a1d4", "h1e1",
+            "a1i3",
+        ] {
+            assert_eq!(
+                text.parse::<Movement>(),
+                Err(ParseMovementError(text.to_string())),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn deserialized_holes_match_the_board() {
+        let holes = Positions::single(27).union_with(Positions::single(36));
+        let board = Board::new(holes);
+        let serialized = Configuration::new(&board).serialize();
+        let configuration = Configuration::try_deserialize(&serialized, &board).unwrap();
+        assert_eq!(configuration.serialize(), serialized);
+        // hole missing from the board
+        let plain = Board::default();
+        assert_eq!(
+            Configuration::try_deserialize(&serialized, &plain).err(),
+            Some(DeserializeError::HoleMismatch(28))
+        );
+        // hole of the board missing from the string
+        let mut cells: Vec<char> = serialized.chars().collect();
+        cells[37] = ' ';
+        let filled: String = cells.iter().collect();
+        assert_eq!(
+            Configuration::try_deserialize(&filled, &board).err(),
+            Some(DeserializeError::HoleMismatch(37))
+        );
+        cells[37] = 'r';
+        let overlapping: String = cells.into_iter().collect();
+        assert_eq!(
+            Configuration::try_deserialize(&overlapping, &board).err(),
+            Some(DeserializeError::Overlapping(37))
+        );
+    }
+
+    #[test]
+    fn illegal_movements_are_skipped() {
+        struct Cheater;
+        impl fmt::Display for Cheater {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "Cheater")
+            }
+        }
+        impl Strategy for Cheater {
+            fn compute_next_move(&mut self, state: &Configuration) -> Option<Movement> {
+                (0..64)
+                    .map(Movement::Duplicate)
+                    .find(|movement| !state.check_move(movement))
+            }
+        }
+        let board = Board::default();
+        let mut state = Configuration::new(&board);
+        let outcome = state.battle_with(Cheater, Cheater, Verbosity::Quiet);
+        assert_eq!(outcome.movements, vec![None, None]);
+        assert!(outcome.record.verify().is_ok());
+    }
+
+    #[test]
+    fn incremental_zobrist_matches_recompute() {
+        let board = Board::default();
+        let mut state = Configuration::new(&board);
+        assert_eq!(state.zobrist(), recomputed(&state));
+        for ply in 0..40 {
+            let movements: Vec<Movement> = state.movements().collect();
+            state = if movements.is_empty() || ply % 7 == 6 {
+                state.skip_play()
+            } else {
+                state.play(&movements[(ply * 5) % movements.len()])
+            };
+            assert_eq!(state.zobrist(), recomputed(&state), "after ply {}", ply);
+        }
+    }
+
+    #[test]
+    fn zobrist_depends_on_player_to_move() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let passed = state.skip_play();
+        assert_ne!(state.zobrist(), passed.zobrist());
+        assert_eq!(state.zobrist(), passed.skip_play().zobrist());
+    }
+}
@@ -0,0 +1,261 @@
+//! A `Game` is a `Configuration` with its history : movements can be taken back and replayed.
+use crate::configuration::{Configuration, Movement};
+use std::fmt;
+
+/// Game in progress with all its plies (passes included).
+/// Taken back plies are kept until another movement is played, so that they can be redone.
+pub struct Game<'a> {
+    /// Configuration before each ply and after the last one.
+    configurations: Vec<Configuration<'a>>,
+    /// Movement of each ply (None for passes).
+    history: Vec<Option<Movement>>,
+    /// Number of plies currently played.
+    ply: usize,
+}
+
+impl<'a> Game<'a> {
+    /// New game starting at given configuration.
+    pub fn new(start: Configuration<'a>) -> Self {
+        Game {
+            configurations: vec![start],
+            history: Vec::new(),
+            ply: 0,
+        }
+    }
+
+    /// Current configuration.
+    pub fn configuration(&self) -> &Configuration<'a> {
+        &self.configurations[self.ply]
+    }
+
+    /// Configuration after given number of plies (if they were played).
+    pub fn configuration_at(&self, ply: usize) -> Option<&Configuration<'a>> {
+        self.configurations.get(ply)
+    }
+
+    /// Number of plies currently played.
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// Movements played so far (None for passes).
+    pub fn history(&self) -> &[Option<Movement>] {
+        &self.history[..self.ply]
+    }
+
+    /// Play given movement (None to pass), forgetting taken back plies.
+    /// Return false (and play nothing) if the movement is illegal. Passing is always allowed,
+    /// like in `battle` where players failing to find a movement pass.
+    pub fn play(&mut self, movement: Option<Movement>) -> bool {
+        let current = *self.configuration();
+        let next = match movement {
+            Some(ref movement) if current.check_move(movement) => current.play(movement),
+            None => current.skip_play(),
+            _ => return false,
+        };
+        self.history.truncate(self.ply);
+        self.configurations.truncate(self.ply + 1);
+        self.history.push(movement);
+        self.configurations.push(next);
+        self.ply += 1;
+        true
+    }
+
+    /// Take back last ply and return its movement (None for a pass).
+    /// Return None if we are at the start.
+    pub fn undo(&mut self) -> Option<Option<Movement>> {
+        if self.ply == 0 {
+            return None;
+        }
+        self.ply -= 1;
+        Some(self.history[self.ply])
+    }
+
+    /// Replay next taken back ply and return its movement (None for a pass).
+    /// Return None if there is nothing to redo.
+    pub fn redo(&mut self) -> Option<Option<Movement>> {
+        let movement = *self.history.get(self.ply)?;
+        self.ply += 1;
+        Some(movement)
+    }
+
+    /// Go to given ply, forward (redoing) or backward (undoing).
+    /// Return false (and move nowhere) if it was never played.
+    pub fn jump(&mut self, ply: usize) -> bool {
+        if ply > self.history.len() {
+            return false;
+        }
+        self.ply = ply;
+        true
+    }
+
+    /// Last ply before the current one where the player to move was the same, if any.
+    /// Jumping to it takes back the last turn of the current player.
+    pub fn previous_turn(&self) -> Option<usize> {
+        let player = self.configuration().current_player;
+        (0..self.ply)
+            .rev()
+            .find(|&ply| self.configurations[ply].current_player == player)
+    }
+}
+
+impl<'a> fmt::Display for Game<'a> {
+    /// History in notation, two plies per numbered turn, like `1. g2 b7 2. -- a8c6`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, movement) in self.history().iter().enumerate() {
+            if index % 2 == 0 {
+                if index > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}.", index / 2 + 1)?;
+            }
+            match movement {
+                Some(movement) => write!(f, " {}", movement)?,
+                None => write!(f, " --")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::configuration::Verbosity;
+    use crate::strategy::{Greedy, Human, Strategy};
+    use std::io;
+
+    /// Play the first movement available `plies` times.
+    fn played<'a>(board: &'a Board, plies: usize) -> Game<'a> {
+        let mut game = Game::new(Configuration::new(board));
+        for _ in 0..plies {
+            let movement = game.configuration().movements().next();
+            assert!(game.play(movement));
+        }
+        game
+    }
+
+    #[test]
+    fn undo_redo_round_trip() {
+        let board = Board::default();
+        let mut game = played(&board, 3);
+        let end = game.configuration().zobrist();
+        let history = game.history().to_vec();
+        for ply in (0..3).rev() {
+            assert_eq!(game.undo(), Some(history[ply]));
+        }
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.ply(), 0);
+        assert_eq!(
+            game.configuration().zobrist(),
+            Configuration::new(&board).zobrist()
+        );
+        for movement in &history {
+            assert_eq!(game.redo(), Some(*movement));
+        }
+        assert_eq!(game.redo(), None);
+        assert_eq!(game.configuration().zobrist(), end);
+        assert_eq!(game.history(), &history[..]);
+    }
+
+    #[test]
+    fn new_movement_drops_taken_back_plies() {
+        let board = Board::default();
+        let mut game = played(&board, 3);
+        game.undo();
+        game.undo();
+        let other = game.configuration().movements().nth(1);
+        assert!(game.play(other));
+        assert_eq!(game.ply(), 2);
+        assert_eq!(game.history()[1], other);
+        assert_eq!(game.redo(), None);
+        assert!(!game.jump(3));
+    }
+
+    #[test]
+    fn illegal_movements_are_refused() {
+        let board = Board::default();
+        let mut game = played(&board, 1);
+        let opponent = Configuration::new(&board).movements().next();
+        // the same movement for the other player is not legal
+        assert!(!game.play(opponent));
+        assert_eq!(game.ply(), 1);
+    }
+
+    #[test]
+    fn jump_out_of_range() {
+        let board = Board::default();
+        let mut game = played(&board, 2);
+        game.undo();
+        assert!(game.jump(2));
+        assert!(game.jump(0));
+        assert!(!game.jump(3));
+        assert_eq!(game.ply(), 0);
+        assert_eq!(game.configuration_at(3).map(Configuration::zobrist), None);
+    }
+
+    #[test]
+    fn previous_turn_across_passes() {
+        let board = Board::default();
+        let mut game = played(&board, 0);
+        assert_eq!(game.previous_turn(), None);
+        let movement = game.configuration().movements().next();
+        game.play(movement);
+        assert_eq!(game.previous_turn(), None);
+        game.play(None);
+        // red plays again : back to its first movement
+        assert_eq!(game.previous_turn(), Some(0));
+        let movement = game.configuration().movements().next();
+        game.play(movement);
+        // blue passed last time
+        assert_eq!(game.previous_turn(), Some(1));
+        game.play(None);
+        game.play(None);
+        assert_eq!(game.previous_turn(), Some(3));
+    }
+
+    #[test]
+    fn notation() {
+        let board = Board::default();
+        let mut game = played(&board, 0);
+        assert_eq!(game.to_string(), "");
+        let first = game.configuration().movements().next().unwrap();
+        game.play(Some(first));
+        assert_eq!(game.to_string(), format!("1. {}", first));
+        game.play(None);
+        let third = game.configuration().movements().next().unwrap();
+        game.play(Some(third));
+        assert_eq!(game.to_string(), format!("1. {} -- 2. {}", first, third));
+        game.undo();
+        assert_eq!(game.to_string(), format!("1. {} --", first));
+    }
+
+    #[test]
+    fn human_takes_back_its_movement() {
+        let board = Board::default();
+        let start = Configuration::new(&board);
+        let mut movements = start.movements();
+        let (first, second) = (movements.next().unwrap(), movements.next().unwrap());
+        // first movement, taken back on the next turn and replaced, then the input is closed
+        let input = format!("{}\nundo\n{}\n", first, second);
+        let mut state = start;
+        let outcome = state.battle_with(
+            Human::with_input(io::Cursor::new(input)),
+            Greedy(),
+            Verbosity::Quiet,
+        );
+        assert_eq!(outcome.movements[0], Some(second));
+        // greedy answered the second movement, not the first one
+        let mut greedy = Greedy();
+        let answer = greedy.compute_next_move(&start.play(&second));
+        assert_eq!(outcome.movements[1], answer);
+        assert!(outcome
+            .movements
+            .iter()
+            .skip(2)
+            .step_by(2)
+            .all(Option::is_none));
+        assert_eq!(outcome.plies, outcome.movements.len());
+    }
+}
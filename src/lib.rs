@@ -4,7 +4,11 @@
 
 pub mod board;
 pub mod configuration;
+pub mod game;
 pub(crate) mod positions;
+pub mod record;
 pub(crate) mod shmem;
+pub mod stats;
 pub mod strategy;
+pub(crate) mod zobrist;
 extern crate rayon;
@@ -1,11 +1,222 @@
 extern crate blobwar;
-//use blobwar::board::Board;
-use blobwar::configuration::Configuration;
-use blobwar::strategy::{Greedy, Human, MinMax, Evil, AlphaBeta};
 
-fn main() {
-    //let board = Board::load("x").expect("failed loading board");
-    let board = Default::default();
+use blobwar::board::Board;
+use blobwar::configuration::{Configuration, Verbosity};
+use blobwar::record::GameRecord;
+use blobwar::strategy::registry::{available_strategies, parse_strategy_with_defaults, STRATEGIES};
+use blobwar::strategy::Strategy;
+
+use std::env::args;
+use std::io::{self, BufRead};
+use std::process::exit;
+
+const USAGE: &str = "usage: blobwar [COMMAND] [OPTIONS]
+commands:
+  play [-b BOARD] [-t MS] [-r RECORD] [-q | -s] [RED] [BLUE]
+      play a game (alphabeta:6 against greedy by default), -t sets the time per move of
+      timed strategies, -r saves the game (as json if RECORD ends with .json), -q hides the
+      game and -s shows searches
+  analyze [-s STRATEGY] POSITION
+      search a serialized position (with alphabeta:6 by default)
+  boards [BOARD...]
+      list and preview boards (all boards of the search path by default, see BLOBWAR_BOARDS)
+  replay RECORD
+      step through a recorded game checking movements, press enter for next move or q to
+      quit
+without command arguments are given to play";
+
+/// Exit with given message and the usage.
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    exit(1)
+}
+
+/// Next argument, which is needed by given option.
+fn value(arguments: &mut impl Iterator<Item = String>, option: &str) -> String {
+    arguments
+        .next()
+        .unwrap_or_else(|| fail(&format!("{} needs a value", option)))
+}
+
+/// Given board or the standard one.
+fn load_board(name: Option<String>) -> Board {
+    name.map_or_else(Board::default, |name| {
+        Board::load(&name)
+            .unwrap_or_else(|e| fail(&format!("failed loading board '{}': {}", name, e)))
+    })
+}
+
+/// Build a strategy, giving it the time limit if it has one and it is not set.
+fn strategy(spec: &str, time: Option<u64>) -> Box<dyn Strategy> {
+    let time = time.map(|ms| ms.to_string());
+    let defaults: Vec<(&str, &str)> = time.iter().map(|ms| ("ms", ms.as_str())).collect();
+    parse_strategy_with_defaults(spec, &defaults).unwrap_or_else(|e| fail(&e.to_string()))
+}
+
+/// Does the strategy of given specification have a time limit (a `ms` parameter) ?
+fn timed(spec: &str) -> bool {
+    let name = spec.split(':').next().unwrap_or_default();
+    STRATEGIES
+        .iter()
+        .filter(|(strategy, _)| *strategy == name)
+        .any(|(_, parameters)| parameters.iter().any(|(parameter, _)| *parameter == "ms"))
+}
+
+fn play(mut arguments: impl Iterator<Item = String>) {
+    let mut board = None;
+    let mut time = None;
+    let mut record = None;
+    let mut verbosity = Verbosity::Normal;
+    let mut specs = Vec::new();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-b" => board = Some(value(&mut arguments, "-b")),
+            "-t" => {
+                time = Some(
+                    value(&mut arguments, "-t")
+                        .parse()
+                        .unwrap_or_else(|_| fail("-t needs a number of milliseconds")),
+                )
+            }
+            "-r" => record = Some(value(&mut arguments, "-r")),
+            "-q" => verbosity = Verbosity::Quiet,
+            "-s" => verbosity = Verbosity::Search,
+            _ => specs.push(argument),
+        }
+    }
+    if specs.len() > 2 {
+        fail("at most two players are needed");
+    }
+    let red_spec = specs.first().map_or("alphabeta:6", String::as_str);
+    let blue_spec = specs.get(1).map_or("greedy", String::as_str);
+    if time.is_some() && !timed(red_spec) && !timed(blue_spec) {
+        fail("-t needs a timed strategy (with a ms parameter, like mcts or iterative)");
+    }
+    let red = strategy(red_spec, time);
+    let blue = strategy(blue_spec, time);
+    let board = load_board(board);
     let mut game = Configuration::new(&board);
-    game.battle(AlphaBeta(6), Greedy());
+    if verbosity == Verbosity::Quiet {
+        println!("{} (red) against {} (blue)", red, blue);
+    }
+    let outcome = game.battle_with(red, blue, verbosity);
+    if verbosity == Verbosity::Quiet {
+        println!(
+            "{} - {} in {} plies",
+            outcome.blobs[0], outcome.blobs[1], outcome.plies
+        );
+    }
+    if let Some(path) = record {
+        if let Err(e) = outcome.record.save(&path) {
+            fail(&format!("failed writing '{}': {}", path, e));
+        }
+    }
+}
+
+fn analyze(mut arguments: impl Iterator<Item = String>) {
+    let mut spec = "alphabeta:6".to_owned();
+    let mut position = None;
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "-s" => spec = value(&mut arguments, "-s"),
+            _ => position = Some(argument),
+        }
+    }
+    let position = position.unwrap_or_else(|| fail("a serialized position is needed"));
+    let board = Board::try_deserialize(&position)
+        .unwrap_or_else(|e| fail(&format!("invalid position: {}", e)));
+    let configuration = Configuration::try_deserialize(&position, &board)
+        .unwrap_or_else(|e| fail(&format!("invalid position: {}", e)));
+    println!("{}", configuration);
+    println!(
+        "{} to play, value {}",
+        ["red", "blue"][configuration.current_player as usize],
+        configuration.value()
+    );
+    let mut player = strategy(&spec, None);
+    let movement = player.compute_next_move(&configuration);
+    if let Some(info) = player.search_info() {
+        println!("{}", info);
+    }
+    match movement {
+        Some(movement) => println!("{} plays {}", player, movement),
+        None => println!("{} passes", player),
+    }
+}
+
+fn boards(arguments: impl Iterator<Item = String>) {
+    let mut names: Vec<String> = arguments.collect();
+    if names.is_empty() {
+        names = Board::names();
+    }
+    for name in names {
+        match Board::load(&name) {
+            Ok(board) => println!("{}{}", name, Configuration::new(&board)),
+            Err(e) => eprintln!("failed loading board '{}': {}", name, e),
+        }
+    }
+}
+
+fn replay(mut arguments: impl Iterator<Item = String>) {
+    let path = arguments
+        .next()
+        .unwrap_or_else(|| fail("a record is needed"));
+    let record = GameRecord::load(&path)
+        .unwrap_or_else(|e| fail(&format!("failed reading '{}': {}", path, e)));
+    println!(
+        "{} (red) against {} (blue)",
+        record.players[0], record.players[1]
+    );
+    let board = record.board().unwrap_or_else(|e| fail(&e.to_string()));
+    let start = record
+        .start(&board)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    println!("{}", start);
+    let replay = record
+        .replay(&board)
+        .unwrap_or_else(|e| fail(&e.to_string()));
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    for (ply, (configuration, recorded)) in replay.zip(&record.moves).enumerate() {
+        if let Some(Ok(line)) = lines.next() {
+            if line.trim() == "q" {
+                return;
+            }
+        }
+        let configuration = configuration.unwrap_or_else(|e| fail(&e.to_string()));
+        // the player who moved is the one not playing next
+        let player = ["blue", "red"][configuration.current_player as usize];
+        match recorded.movement {
+            Some(movement) => print!("ply {}: {} plays {}", ply + 1, player, movement),
+            None => print!("ply {}: {} passes", ply + 1, player),
+        }
+        if let Some(score) = recorded.score {
+            print!(", score {}", score);
+        }
+        if let Some(ref comment) = recorded.comment {
+            print!(" ({})", comment);
+        }
+        println!("{}", configuration);
+    }
+    if let Some([red, blue]) = record.result {
+        println!("result: {} - {}", red, blue);
+    }
+}
+
+fn main() {
+    let mut arguments = args().skip(1).peekable();
+    let command = arguments.peek().cloned().unwrap_or_default();
+    match command.as_str() {
+        "play" | "analyze" | "boards" | "replay" => {
+            arguments.next();
+        }
+        "-h" | "--help" => fail(&format!("strategies:\n{}", available_strategies())),
+        _ => {}
+    }
+    match command.as_str() {
+        "analyze" => analyze(arguments),
+        "boards" => boards(arguments),
+        "replay" => replay(arguments),
+        _ => play(arguments),
+    }
 }
@@ -99,6 +99,14 @@ impl Positions {
     pub fn single(position: Position) -> Self {
         Positions(1u64 << position)
     }
+    /// Return us and all `Position`s at distance 1 of us (holes are not taken into account).
+    pub fn dilate(&self) -> Positions {
+        // masks removing bits which wrapped around to the other side of the board
+        const NOT_FIRST_COLUMN: u64 = 0xfefe_fefe_fefe_fefe;
+        const NOT_LAST_COLUMN: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+        let row = self.0 | (self.0 << 1 & NOT_FIRST_COLUMN) | (self.0 >> 1 & NOT_LAST_COLUMN);
+        Positions(row | row << 8 | row >> 8)
+    }
     /// Do we contain every possible `Position` ?
     pub fn is_all(&self) -> bool {
         self.0 == std::u64::MAX
@@ -142,3 +150,46 @@ impl fmt::Display for Positions {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// All positions at distance at most 1 of given one.
+    fn neighbourhood(position: Position) -> u64 {
+        (0..64)
+            .filter(|&other: &Position| position.distance_to(other) <= 1)
+            .fold(0, |set, other| set | 1 << other)
+    }
+
+    #[test]
+    fn dilate_corners_and_edges() {
+        let dilated = |position| Positions::single(position).dilate().0;
+        let set = |positions: &[Position]| positions.iter().fold(0, |set, p| set | 1 << p);
+        // a8, h8, a1 and h1
+        assert_eq!(dilated(0), set(&[0, 1, 8, 9]));
+        assert_eq!(dilated(7), set(&[6, 7, 14, 15]));
+        assert_eq!(dilated(56), set(&[48, 49, 56, 57]));
+        assert_eq!(dilated(63), set(&[54, 55, 62, 63]));
+        // a4 and h5 do not wrap around to the other side
+        assert_eq!(dilated(32), set(&[24, 25, 32, 33, 40, 41]));
+        assert_eq!(dilated(31), set(&[22, 23, 30, 31, 38, 39]));
+    }
+
+    #[test]
+    fn dilate_every_cell() {
+        for position in 0..64 {
+            assert_eq!(
+                Positions::single(position).dilate().0,
+                neighbourhood(position),
+                "{}",
+                position
+            );
+        }
+        assert!(Positions(0).dilate().is_empty());
+        assert!(Positions(std::u64::MAX).dilate().is_all());
+        // the first and last columns only grow inwards
+        let columns = Positions(0x8181_8181_8181_8181).dilate();
+        assert_eq!(columns.0, 0xc3c3_c3c3_c3c3_c3c3);
+    }
+}
@@ -0,0 +1,386 @@
+//! Records of played games, to save them and replay them later.
+//!
+//! Records are stored either as JSON or in a text form :
+//!
+//! ```text
+//! red: Alpha - Beta (max level: 6, material evaluation)
+//! blue: Greedy
+//! start: 0r......b.....
+//! result: 38 - 26
+//! g2 {3}
+//! a8c6 {#+5 (+12)} ; sure win
+//! --
+//! ```
+//!
+//! The start position is the serialized `Configuration` (which includes the board holes) with
+//! empty cells written as dots. Each following line is one ply : a movement in Ataxx notation
+//! (`c3` duplicates to c3, `a1c3` jumps from a1 to c3) or `--` for a pass, then an optional
+//! score between braces and an optional comment after a semicolon.
+use crate::board::Board;
+use crate::configuration::{parse_serialized, Configuration, DeserializeError, Movement};
+use crate::strategy::Score;
+use serde_derive::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+/// One ply of a recorded game.
+pub struct RecordedMove {
+    /// Movement played, None for a pass.
+    pub movement: Option<Movement>,
+    /// Score reported by the search behind the movement, if any.
+    pub score: Option<Score>,
+    /// Free comment.
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+/// Everything needed to replay a game.
+pub struct GameRecord {
+    /// Names of red and blue players.
+    pub players: [String; 2],
+    /// Serialized starting `Configuration`, board holes included.
+    pub start: String,
+    /// All plies, passes included.
+    pub moves: Vec<RecordedMove>,
+    /// Final number of red and blue blobs, None if the game is not over.
+    pub result: Option<[i8; 2]>,
+}
+
+#[derive(Debug)]
+/// Everything which can go wrong with records.
+pub enum RecordError {
+    /// Reading or writing the file failed.
+    Io(io::Error),
+    /// Invalid JSON.
+    Json(serde_json::Error),
+    /// Invalid line (numbered from 1) in the text form.
+    Syntax(usize, String),
+    /// The text form has no start position.
+    MissingStart,
+    /// The start position is not a serialized `Configuration`.
+    InvalidStart(DeserializeError),
+    /// Movement (numbered from 1) which is not legal in the replayed game.
+    IllegalMove(usize, Movement),
+    /// The replayed game does not end with the recorded result.
+    WrongResult([i8; 2]),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::Io(e) => write!(f, "{}", e),
+            RecordError::Json(e) => write!(f, "invalid json record: {}", e),
+            RecordError::Syntax(line, message) => write!(f, "line {}: {}", line, message),
+            RecordError::MissingStart => write!(f, "missing start position"),
+            RecordError::InvalidStart(e) => write!(f, "invalid start position: {}", e),
+            RecordError::IllegalMove(ply, movement) => {
+                write!(f, "illegal movement {} at ply {}", movement, ply)
+            }
+            RecordError::WrongResult([red, blue]) => {
+                write!(f, "game ends {} - {} instead of recorded result", red, blue)
+            }
+        }
+    }
+}
+
+impl Error for RecordError {}
+
+impl From<io::Error> for RecordError {
+    fn from(error: io::Error) -> Self {
+        RecordError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for RecordError {
+    fn from(error: serde_json::Error) -> Self {
+        RecordError::Json(error)
+    }
+}
+
+impl GameRecord {
+    /// New record of a game starting at given configuration.
+    pub fn new(start: &Configuration, players: [String; 2]) -> Self {
+        GameRecord {
+            players,
+            start: start.serialize(),
+            moves: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// Board the game is played on.
+    pub fn board(&self) -> Result<Board, RecordError> {
+        Board::try_deserialize(&self.start).map_err(RecordError::InvalidStart)
+    }
+
+    /// Starting configuration on given board (obtained with `board`).
+    pub fn start<'a>(&self, board: &'a Board) -> Result<Configuration<'a>, RecordError> {
+        Configuration::try_deserialize(&self.start, board).map_err(RecordError::InvalidStart)
+    }
+
+    /// Replay the game on given board (obtained with `board`), yielding the configuration
+    /// after each ply. Movements are checked with `check_move`, passes are always accepted
+    /// since players failing to find a movement pass (see `battle_with`).
+    pub fn replay<'a>(&'a self, board: &'a Board) -> Result<Replay<'a>, RecordError> {
+        Ok(Replay {
+            configuration: self.start(board)?,
+            moves: self.moves.iter().enumerate(),
+        })
+    }
+
+    /// Replay the whole game and check the recorded result.
+    pub fn verify(&self) -> Result<(), RecordError> {
+        let board = self.board()?;
+        let mut last = self.start(&board)?;
+        for configuration in self.replay(&board)? {
+            last = configuration?;
+        }
+        let blobs = [last.blobs(false).len(), last.blobs(true).len()];
+        match self.result {
+            Some(result) if result != blobs => Err(RecordError::WrongResult(blobs)),
+            _ => Ok(()),
+        }
+    }
+
+    /// JSON form.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("failed serializing record")
+    }
+
+    /// Read the JSON form.
+    pub fn from_json(json: &str) -> Result<Self, RecordError> {
+        let record: GameRecord = serde_json::from_str(json)?;
+        parse_serialized(&record.start).map_err(RecordError::InvalidStart)?;
+        Ok(record)
+    }
+
+    /// Save to given file, as JSON if its extension is `json` and as text otherwise.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RecordError> {
+        let contents = if is_json(path.as_ref()) {
+            self.to_json()
+        } else {
+            self.to_string()
+        };
+        Ok(fs::write(path, contents)?)
+    }
+
+    /// Load from given file, as JSON if its extension is `json` and as text otherwise.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RecordError> {
+        let contents = fs::read_to_string(path.as_ref())?;
+        if is_json(path.as_ref()) {
+            GameRecord::from_json(&contents)
+        } else {
+            contents.parse()
+        }
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+/// Iterator on the configurations of a replayed game.
+pub struct Replay<'a> {
+    configuration: Configuration<'a>,
+    moves: std::iter::Enumerate<std::slice::Iter<'a, RecordedMove>>,
+}
+
+impl<'a> Iterator for Replay<'a> {
+    type Item = Result<Configuration<'a>, RecordError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (index, recorded) = self.moves.next()?;
+        let ply = index + 1;
+        match recorded.movement {
+            Some(movement) if !self.configuration.check_move(&movement) => {
+                return Some(Err(RecordError::IllegalMove(ply, movement)))
+            }
+            Some(movement) => self.configuration.apply_movement(&movement),
+            None => self.configuration = self.configuration.skip_play(),
+        }
+        Some(Ok(self.configuration))
+    }
+}
+
+fn write_movement(f: &mut fmt::Formatter, movement: &Option<Movement>) -> fmt::Result {
+    match movement {
+        Some(movement) => write!(f, "{}", movement),
+        None => write!(f, "--"),
+    }
+}
+
+fn parse_movement(text: &str) -> Option<Option<Movement>> {
+    match text {
+        "--" => Some(None),
+        _ => text.parse().ok().map(Some),
+    }
+}
+
+/// Read a line of the text form, except headers.
+fn parse_move(line: &str) -> Option<RecordedMove> {
+    let (line, comment) = match line.split_once(';') {
+        Some((line, comment)) => (line.trim(), Some(comment.trim().to_owned())),
+        None => (line, None),
+    };
+    let (movement, score) = match line.split_once('{') {
+        Some((movement, score)) => (
+            movement.trim(),
+            Some(score.strip_suffix('}')?.parse().ok()?),
+        ),
+        None => (line, None),
+    };
+    Some(RecordedMove {
+        movement: parse_movement(movement)?,
+        score,
+        comment,
+    })
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "red: {}", self.players[0])?;
+        writeln!(f, "blue: {}", self.players[1])?;
+        writeln!(f, "start: {}", self.start.replace(' ', "."))?;
+        if let Some([red, blue]) = self.result {
+            writeln!(f, "result: {} - {}", red, blue)?;
+        }
+        for recorded in &self.moves {
+            write_movement(f, &recorded.movement)?;
+            if let Some(score) = recorded.score {
+                write!(f, " {{{}}}", score)?;
+            }
+            if let Some(ref comment) = recorded.comment {
+                write!(f, " ; {}", comment)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+    /// Read the text form.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut players = [String::new(), String::new()];
+        let mut start = None;
+        let mut result = None;
+        let mut moves = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let syntax = |message: &str| RecordError::Syntax(index + 1, message.to_owned());
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            // headers, other lines are movements (which may have colons in their comment)
+            match line.split_once(": ") {
+                Some(("red", value)) => players[0] = value.to_owned(),
+                Some(("blue", value)) => players[1] = value.to_owned(),
+                Some(("start", value)) => start = Some(value.replace('.', " ")),
+                Some(("result", value)) => {
+                    let (red, blue) = value
+                        .split_once(" - ")
+                        .and_then(|(red, blue)| Some((red.parse().ok()?, blue.parse().ok()?)))
+                        .ok_or_else(|| syntax("invalid result"))?;
+                    result = Some([red, blue]);
+                }
+                _ => {
+                    moves.push(parse_move(line).ok_or_else(|| syntax("invalid movement"))?);
+                }
+            }
+        }
+        let start = start.ok_or(RecordError::MissingStart)?;
+        parse_serialized(&start).map_err(RecordError::InvalidStart)?;
+        Ok(GameRecord {
+            players,
+            start,
+            moves,
+            result,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ply(movement: Option<Movement>) -> RecordedMove {
+        RecordedMove {
+            movement,
+            score: None,
+            comment: None,
+        }
+    }
+
+    fn record(moves: Vec<Option<Movement>>) -> GameRecord {
+        let board = Board::default();
+        let mut record = GameRecord::new(
+            &Configuration::new(&board),
+            ["red".to_owned(), "blue".to_owned()],
+        );
+        record.moves = moves.into_iter().map(ply).collect();
+        record
+    }
+
+    #[test]
+    fn invalid_start() {
+        let mut broken = record(Vec::new());
+        broken.start = "not a position".to_owned();
+        assert!(matches!(broken.board(), Err(RecordError::InvalidStart(_))));
+        assert!(matches!(broken.verify(), Err(RecordError::InvalidStart(_))));
+        let board = Board::default();
+        assert!(matches!(
+            broken.start(&board),
+            Err(RecordError::InvalidStart(_))
+        ));
+        assert!(broken.replay(&board).is_err());
+    }
+
+    #[test]
+    fn passes_replay_like_battles() {
+        let board = Board::default();
+        let start = Configuration::new(&board);
+        let first = start.movements().next().unwrap();
+        // the second player passes although it could move
+        let game = record(vec![Some(first), None]);
+        let replayed: Vec<Configuration> = game
+            .replay(&board)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(replayed.len(), 2);
+        assert_eq!(replayed[1].current_player, start.current_player);
+        assert!(game.verify().is_ok());
+    }
+
+    #[test]
+    fn illegal_movements() {
+        let board = Board::default();
+        let start = Configuration::new(&board);
+        let illegal = (0..64)
+            .map(Movement::Duplicate)
+            .find(|movement| !start.check_move(movement))
+            .unwrap();
+        let game = record(vec![None, Some(illegal)]);
+        match game.verify() {
+            Err(RecordError::IllegalMove(2, movement)) => assert_eq!(movement, illegal),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn text_and_json_round_trips() {
+        let board = Board::default();
+        let start = Configuration::new(&board);
+        let mut game = record(vec![start.movements().next(), None]);
+        game.moves[0].score = Some(Score::finished(12, 5));
+        game.moves[1].comment = Some("thinking".to_owned());
+        assert_eq!(game.to_string().parse::<GameRecord>().unwrap(), game);
+        assert_eq!(GameRecord::from_json(&game.to_json()).unwrap(), game);
+    }
+}
@@ -1,49 +1,190 @@
-//! Provide methods sharing a `Movement` in shared memory between processes.
-//! Slave anytime process writes new moves atomically and master process only reads them.
+//! Provide methods sharing the progress of a search in shared memory between processes.
+//! Slave anytime process writes a report (movement, depth, score, nodes and principal
+//! variation) after each completed depth and master process only reads them.
+//!
+//! Each segment has a unique name (containing the pid of its creator) which the master passes
+//! to the slave, so that several searches can run at the same time. Segments left behind by
+//! crashed processes are removed when the next one is created.
 use super::configuration::Movement;
+use super::strategy::{Score, SearchInfo};
 use libc::off_t;
 use libc::{c_void, size_t};
 use nix;
-use nix::fcntl::{O_CREAT, O_RDWR};
+use nix::errno::Errno;
+use nix::fcntl::{O_CREAT, O_EXCL, O_RDWR};
 use nix::sys::mman::MAP_SHARED;
 use nix::sys::mman::{mmap, munmap};
 use nix::sys::mman::{shm_open, shm_unlink};
 use nix::sys::mman::{PROT_READ, PROT_WRITE};
 use nix::sys::stat::{S_IRUSR, S_IWUSR};
 use nix::unistd::{close, ftruncate};
+use std::cell::UnsafeCell;
+use std::fs;
+use std::hint;
+use std::io;
 use std::os::unix::io::RawFd;
+use std::process;
 use std::ptr;
+use std::time::Duration;
 
-use std::mem;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::mem::{self, MaybeUninit};
+use std::sync::atomic::{fence, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Once;
 
+/// Prefix of all segment names.
+const PREFIX: &str = "blobwar-";
+
+/// Directory where the system exposes shared memory segments.
+const SHM_DIRECTORY: &str = "/dev/shm";
+
+/// Number of segments created so far by this process.
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Stale segments are only looked for once per process.
+static CLEANUP: Once = Once::new();
+
+/// Version of the segments layout, to change with `Record` or `InnerAtomicMove` so that
+/// processes built from different sources refuse to talk to each other.
+const VERSION: u32 = 1;
+
+/// Maximal number of movements of shared principal variations.
+pub const PV_LENGTH: usize = 16;
+
+#[derive(Copy, Clone)]
+/// Report of a completed depth, as stored in a segment.
+struct Record {
+    movement: Option<Movement>,
+    depth: u8,
+    score: Score,
+    nodes: u64,
+    cutoffs: u64,
+    elapsed: Duration,
+    exact: bool,
+    /// Start of the principal variation, padded with None.
+    pv: [Option<Movement>; PV_LENGTH],
+}
+
+impl<'a> From<&'a SearchInfo> for Record {
+    fn from(info: &'a SearchInfo) -> Self {
+        let mut pv = [None; PV_LENGTH];
+        for (cell, movement) in pv.iter_mut().zip(&info.pv) {
+            *cell = Some(*movement);
+        }
+        Record {
+            movement: info.movement(),
+            depth: info.depth,
+            score: info.score,
+            nodes: info.nodes,
+            cutoffs: info.cutoffs,
+            elapsed: info.elapsed,
+            exact: info.exact,
+            pv,
+        }
+    }
+}
+
+impl From<Record> for SearchInfo {
+    fn from(record: Record) -> Self {
+        let mut pv: Vec<Movement> = record.pv.iter().map_while(|movement| *movement).collect();
+        if pv.is_empty() {
+            pv.extend(record.movement);
+        }
+        SearchInfo {
+            depth: record.depth,
+            score: record.score,
+            nodes: record.nodes,
+            cutoffs: record.cutoffs,
+            elapsed: record.elapsed,
+            pv,
+            exact: record.exact,
+        }
+    }
+}
+
+/// A `Record` protected by a seqlock : its sequence number is odd while it is written and
+/// readers retry if it changed while they were copying the record.
+struct Slot {
+    sequence: AtomicUsize,
+    record: UnsafeCell<MaybeUninit<Record>>,
+}
+
+/// Content of a segment.
+/// The writer alternates between two slots, so that the last published record stays
+/// readable when the writer is killed in the middle of writing the next one.
 struct InnerAtomicMove {
-    movements: [Option<Movement>; 2],
-    selected: AtomicUsize,
+    version: AtomicU32,
+    /// Number of records written so far, the last one is in slot `(published - 1) % 2`.
+    published: AtomicUsize,
+    slots: [Slot; 2],
 }
 
 pub struct AtomicMove {
+    name: String,
     fd: RawFd,
     created: bool,
     address: *mut c_void,
 }
 
 impl Drop for AtomicMove {
+    /// Release the segment. Failures are only reported : there is nothing left to do about
+    /// them and panicking in a destructor could abort the process.
     fn drop(&mut self) {
         let size = mem::size_of::<InnerAtomicMove>();
-        close(self.fd).unwrap();
-        munmap(self.address, size as size_t).unwrap();
-
+        if let Err(e) = close(self.fd) {
+            eprintln!("failed closing shared memory {}: {}", self.name, e);
+        }
+        if let Err(e) = munmap(self.address, size as size_t) {
+            eprintln!("failed unmapping shared memory {}: {}", self.name, e);
+        }
         if self.created {
-            shm_unlink("blobwar").unwrap();
+            if let Err(e) = shm_unlink(self.name.as_str()) {
+                eprintln!("failed removing shared memory {}: {}", self.name, e);
+            }
         }
     }
 }
 
+/// Is there a process with given pid ?
+fn is_alive(pid: i32) -> bool {
+    let signaled = unsafe { libc::kill(pid, 0) } == 0;
+    signaled || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Remove the segments created by processes which are not running anymore
+/// (they crashed or were killed before unlinking them).
+/// Return how many were removed.
+pub fn remove_stale_segments() -> usize {
+    let entries = match fs::read_dir(SHM_DIRECTORY) {
+        Ok(entries) => entries,
+        Err(_) => return 0, // segments are not visible on this system
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| {
+            name.strip_prefix(PREFIX)
+                .and_then(|suffix| suffix.split('-').next())
+                .and_then(|pid| pid.parse().ok())
+                .is_some_and(|pid| !is_alive(pid))
+        })
+        .filter(|name| shm_unlink(format!("/{}", name).as_str()).is_ok())
+        .count()
+}
+
 impl AtomicMove {
+    /// Create a new segment with a unique name (see `name`).
     pub fn new() -> Result<Self, nix::Error> {
+        CLEANUP.call_once(|| {
+            remove_stale_segments();
+        });
+        let name = format!(
+            "/{}{}-{}",
+            PREFIX,
+            process::id(),
+            CREATED.fetch_add(1, Ordering::SeqCst)
+        );
         let size = mem::size_of::<InnerAtomicMove>();
-        let fd = shm_open("blobwar", O_CREAT | O_RDWR, S_IRUSR | S_IWUSR)?;
+        let fd = shm_open(name.as_str(), O_CREAT | O_EXCL | O_RDWR, S_IRUSR | S_IWUSR)?;
         ftruncate(fd, size as off_t)?;
 
         let address = mmap(
@@ -55,22 +196,25 @@ impl AtomicMove {
             0,
         )?;
 
-        let atomic: &mut InnerAtomicMove =
-            unsafe { (address as *mut InnerAtomicMove).as_mut().unwrap() };
-        atomic.movements[0] = None;
-        atomic.movements[1] = None;
-        atomic.selected = Default::default();
-
-        Ok(AtomicMove {
+        // the segment is filled with zeros : no record is published yet
+        let atomic = AtomicMove {
+            name,
             fd,
             address,
             created: true,
-        })
+        };
+        atomic.inner().version.store(VERSION, Ordering::Release);
+        Ok(atomic)
+    }
+    /// Name to give to `connect` to reach this segment.
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    pub fn connect() -> Result<Self, nix::Error> {
+    /// Connect to the segment with given name, created by another process.
+    pub fn connect(name: &str) -> Result<Self, nix::Error> {
         let size = mem::size_of::<InnerAtomicMove>();
-        let fd = shm_open("blobwar", O_RDWR, S_IRUSR | S_IWUSR)?;
+        let fd = shm_open(name, O_RDWR, S_IRUSR | S_IWUSR)?;
         let address = mmap(
             ptr::null_mut(),
             size as size_t,
@@ -79,24 +223,149 @@ impl AtomicMove {
             fd,
             0,
         )?;
-        Ok(AtomicMove {
+        let atomic = AtomicMove {
+            name: name.to_owned(),
             fd,
             address,
             created: false,
-        })
+        };
+        if atomic.inner().version.load(Ordering::Acquire) != VERSION {
+            return Err(nix::Error::Sys(Errno::EPROTO));
+        }
+        Ok(atomic)
+    }
+
+    fn inner(&self) -> &InnerAtomicMove {
+        // only accessed through atomics and the seqlock of each slot
+        unsafe { &*(self.address as *const InnerAtomicMove) }
+    }
+
+    /// Publish the report of a completed depth (only one process may write).
+    pub fn store(&mut self, info: &SearchInfo) {
+        let inner = self.inner();
+        let published = inner.published.load(Ordering::Relaxed);
+        let slot = &inner.slots[published % 2];
+        let sequence = slot.sequence.load(Ordering::Relaxed);
+        slot.sequence.store(sequence + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        unsafe { ptr::write_volatile(slot.record.get(), MaybeUninit::new(Record::from(info))) };
+        slot.sequence.store(sequence + 2, Ordering::Release);
+        inner.published.store(published + 1, Ordering::Release);
+    }
+
+    /// Last published report, None if no depth was completed.
+    pub fn load(&self) -> Option<SearchInfo> {
+        let inner = self.inner();
+        loop {
+            let published = inner.published.load(Ordering::Acquire);
+            if published == 0 {
+                return None;
+            }
+            let slot = &inner.slots[(published - 1) % 2];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            if sequence.is_multiple_of(2) {
+                let record = unsafe { ptr::read_volatile(slot.record.get()) };
+                fence(Ordering::Acquire);
+                if slot.sequence.load(Ordering::Relaxed) == sequence {
+                    return Some(unsafe { record.assume_init() }.into());
+                }
+            }
+            // the writer lapped us and is rewriting this slot
+            hint::spin_loop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::configuration::Configuration;
+    use crate::strategy::{AlphaBeta, DepthSearch, Stop};
+    use std::thread;
+
+    #[test]
+    fn concurrent_searches() {
+        let board = Board::default();
+        let start = Configuration::new(&board);
+        let openings: Vec<Movement> = start.movements().collect();
+        thread::scope(|scope| {
+            for opening in openings.iter().take(6) {
+                let state = start.play(opening);
+                scope.spawn(move || {
+                    let segment = AtomicMove::new().expect("failed creating shmem");
+                    assert!(segment.load().is_none());
+                    let name = segment.name();
+                    // the search runs on its own thread and only knows the segment name
+                    let last = thread::scope(|search| {
+                        search
+                            .spawn(|| {
+                                let mut shared = AtomicMove::connect(name).unwrap();
+                                let mut strategy = AlphaBeta::new(1);
+                                let mut last = None;
+                                for depth in 1..=3 {
+                                    last = strategy.search_depth(&state, depth, &Stop::default());
+                                    shared.store(last.as_ref().unwrap());
+                                }
+                                last.unwrap()
+                            })
+                            .join()
+                            .unwrap()
+                    });
+                    let info = segment.load().unwrap();
+                    assert_eq!(info.depth, 3);
+                    assert_eq!(info.score, last.score);
+                    assert_eq!(info.nodes, last.nodes);
+                    assert_eq!(info.pv, last.pv);
+                    assert!(state.check_move(&info.movement().unwrap()));
+                });
+            }
+        });
     }
 
-    pub fn store(&mut self, movement: Option<Movement>) {
-        let atomic: &mut InnerAtomicMove =
-            unsafe { (self.address as *mut InnerAtomicMove).as_mut().unwrap() };
-        let index = atomic.selected.load(Ordering::SeqCst);
-        atomic.movements[(index + 1) % 2] = movement;
-        atomic.selected.fetch_add(1, Ordering::SeqCst);
+    #[test]
+    fn no_torn_reads() {
+        let segment = AtomicMove::new().unwrap();
+        let name = segment.name();
+        let done = std::sync::atomic::AtomicBool::new(false);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let mut writer = AtomicMove::connect(name).unwrap();
+                // every field holds the same number
+                for count in 0..100_000u64 {
+                    writer.store(&SearchInfo {
+                        depth: count as u8,
+                        nodes: count,
+                        cutoffs: count,
+                        pv: vec![Movement::Duplicate(count as u8 % 64); PV_LENGTH],
+                        ..Default::default()
+                    });
+                }
+                done.store(true, Ordering::Relaxed);
+            });
+            while !done.load(Ordering::Relaxed) {
+                if let Some(info) = segment.load() {
+                    assert_eq!(info.nodes, info.cutoffs);
+                    assert_eq!(info.depth, info.nodes as u8);
+                    assert!(info
+                        .pv
+                        .iter()
+                        .all(|movement| *movement == Movement::Duplicate(info.nodes as u8 % 64)));
+                }
+            }
+        });
+        assert_eq!(segment.load().unwrap().nodes, 99_999);
     }
 
-    pub fn load(&self) -> Option<Movement> {
-        let atomic: &InnerAtomicMove =
-            unsafe { (self.address as *const InnerAtomicMove).as_ref().unwrap() };
-        atomic.movements[atomic.selected.load(Ordering::SeqCst) % 2]
+    #[test]
+    fn stale_segments_are_removed() {
+        // no process can have the largest pid
+        let name = format!("/{}{}-0", PREFIX, i32::MAX);
+        let fd = shm_open(name.as_str(), O_CREAT | O_RDWR, S_IRUSR | S_IWUSR).unwrap();
+        close(fd).unwrap();
+        let segment = AtomicMove::new().unwrap();
+        remove_stale_segments();
+        assert!(shm_unlink(name.as_str()).is_err());
+        assert!(AtomicMove::connect(segment.name()).is_ok());
     }
 }
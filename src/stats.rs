@@ -0,0 +1,272 @@
+//! Statistics on match results between two players.
+//! Results are always counted from the point of view of the first player.
+
+/// Elo difference giving given expected score (between 0 and 1).
+pub fn elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Expected score of a player with given Elo advantage.
+pub fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+/// Wins, draws and losses of the first player.
+pub struct Tally {
+    /// Games won.
+    pub wins: u32,
+    /// Games drawn.
+    pub draws: u32,
+    /// Games lost.
+    pub losses: u32,
+}
+
+impl Tally {
+    /// Count a game with given margin (positive if the first player won).
+    pub fn add(&mut self, margin: i8) {
+        match margin {
+            m if m > 0 => self.wins += 1,
+            0 => self.draws += 1,
+            _ => self.losses += 1,
+        }
+    }
+
+    /// Number of games played.
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Average score (1 per win, 0.5 per draw).
+    pub fn score(&self) -> f64 {
+        (f64::from(self.wins) + f64::from(self.draws) / 2.0) / f64::from(self.games().max(1))
+    }
+
+    /// Estimated Elo difference and half width of its 95% confidence interval.
+    /// Estimates are infinite when one player won all games, error bars when the interval
+    /// reaches such a score.
+    pub fn elo(&self) -> (f64, f64) {
+        let games = f64::from(self.games().max(1));
+        let score = self.score();
+        let deviation = |result: f64, count: u32| f64::from(count) * (result - score).powi(2);
+        let variance =
+            (deviation(1.0, self.wins) + deviation(0.5, self.draws) + deviation(0.0, self.losses))
+                / games;
+        let error = 1.96 * (variance / games).sqrt();
+        let low = elo((score - error).max(0.0));
+        let high = elo((score + error).min(1.0));
+        let width = (high - low) / 2.0;
+        (
+            elo(score),
+            if width.is_nan() { f64::INFINITY } else { width },
+        )
+    }
+}
+
+/// Score of a single game in half points, from the first player's margin.
+fn half_points(margin: i8) -> usize {
+    match margin {
+        m if m > 0 => 2,
+        0 => 1,
+        _ => 0,
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+/// Results of pairs of games played from the same opening with swapped colors.
+/// Pairs are classified by the number of half points scored by the first player (0 to 4),
+/// which accounts for the correlation between both games of a pair.
+pub struct Pairs {
+    /// Number of pairs for each score.
+    pub counts: [u32; 5],
+}
+
+impl Pairs {
+    /// Count a pair given the margins of the first player in both games.
+    pub fn add(&mut self, first: i8, second: i8) {
+        self.counts[half_points(first) + half_points(second)] += 1;
+    }
+
+    /// Number of pairs played.
+    pub fn pairs(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    /// Mean and variance of the score of a pair (between 0 and 1).
+    /// Each class gets an extra half pair so that the variance never vanishes and that a
+    /// few lucky games are not enough to conclude.
+    fn distribution(&self) -> (f64, f64) {
+        let weights: Vec<f64> = self
+            .counts
+            .iter()
+            .map(|&count| f64::from(count) + 0.5)
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let score = |class: usize| class as f64 / 4.0;
+        let mean = weights
+            .iter()
+            .enumerate()
+            .map(|(class, weight)| weight * score(class))
+            .sum::<f64>()
+            / total;
+        let variance = weights
+            .iter()
+            .enumerate()
+            .map(|(class, weight)| weight * (score(class) - mean).powi(2))
+            .sum::<f64>()
+            / total;
+        (mean, variance)
+    }
+}
+
+/// Possible conclusions of a sequential probability ratio test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The first player is at least `elo1` stronger.
+    Accepted,
+    /// The first player is no more than `elo0` stronger.
+    Rejected,
+}
+
+#[derive(Clone, Copy, Debug)]
+/// Sequential probability ratio test between hypotheses H0 (Elo difference is `elo0`)
+/// and H1 (Elo difference is `elo1`), with error rates `alpha` (accepting H1 wrongly)
+/// and `beta` (rejecting it wrongly).
+/// The log-likelihood ratio uses the usual normal approximation on pair results.
+pub struct Sprt {
+    /// Elo difference under H0.
+    pub elo0: f64,
+    /// Elo difference under H1.
+    pub elo1: f64,
+    /// False positive rate.
+    pub alpha: f64,
+    /// False negative rate.
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt {
+            elo0: 0.0,
+            elo1: 20.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+impl Sprt {
+    /// Log-likelihood ratio thresholds for rejecting and accepting H1.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Log-likelihood ratio of H1 against H0 for given results.
+    pub fn llr(&self, pairs: &Pairs) -> f64 {
+        if pairs.pairs() == 0 {
+            return 0.0;
+        }
+        let (score, variance) = pairs.distribution();
+        let score0 = expected_score(self.elo0);
+        let score1 = expected_score(self.elo1);
+        f64::from(pairs.pairs()) * (score1 - score0) * (2.0 * score - score0 - score1)
+            / (2.0 * variance)
+    }
+
+    /// Conclusion reached with given results, if any.
+    pub fn verdict(&self, pairs: &Pairs) -> Option<Verdict> {
+        let (lower, upper) = self.bounds();
+        let llr = self.llr(pairs);
+        if llr >= upper {
+            Some(Verdict::Accepted)
+        } else if llr <= lower {
+            Some(Verdict::Rejected)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn elo_of_known_scores() {
+        assert!(close(elo(0.5), 0.0));
+        assert!(close(elo(0.75), 190.848_501_887));
+        assert!(close(elo(0.25), -190.848_501_887));
+        assert!(close(expected_score(20.0), 0.528_750_563_892));
+        for &score in &[0.1, 0.3, 0.5, 0.7, 0.9] {
+            assert!(close(expected_score(elo(score)), score));
+        }
+    }
+
+    #[test]
+    fn tally_elo() {
+        let mut tally = Tally::default();
+        for &margin in &[3, 1, 64, 5, 2, 9, 0, 0, -4, -1] {
+            tally.add(margin);
+        }
+        assert_eq!((tally.wins, tally.draws, tally.losses), (6, 2, 2));
+        assert!(close(tally.score(), 0.7));
+        let (estimate, error) = tally.elo();
+        assert!(close(estimate, 147.190_714_117));
+        assert!(close(error, 268.725_933_943));
+        let sweep = Tally {
+            wins: 10,
+            ..Default::default()
+        };
+        assert_eq!(sweep.elo(), (f64::INFINITY, f64::INFINITY));
+    }
+
+    #[test]
+    fn sprt_llr() {
+        let sprt = Sprt::default();
+        let (lower, upper) = sprt.bounds();
+        assert!(close(lower, (0.05f64 / 0.95).ln()));
+        assert!(close(upper, 19f64.ln()));
+        assert_eq!(sprt.llr(&Pairs::default()), 0.0);
+        let even = Pairs {
+            counts: [0, 0, 10, 0, 0],
+        };
+        assert!(close(sprt.llr(&even), -0.165_318_984_824));
+        let better = Pairs {
+            counts: [0, 0, 0, 10, 0],
+        };
+        assert!(close(sprt.llr(&better), 1.524_804_376_112));
+        let mut pairs = Pairs::default();
+        pairs.add(1, -1);
+        pairs.add(5, 0);
+        assert_eq!(pairs.counts, [0, 0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn sprt_verdicts() {
+        let sprt = Sprt::default();
+        let results = |class: usize, count: u32| {
+            let mut pairs = Pairs::default();
+            pairs.counts[class] = count;
+            pairs
+        };
+        assert_eq!(sprt.verdict(&results(2, 3)), None);
+        assert_eq!(sprt.verdict(&results(3, 3)), None);
+        assert_eq!(sprt.verdict(&results(4, 100)), Some(Verdict::Accepted));
+        assert_eq!(sprt.verdict(&results(0, 100)), Some(Verdict::Rejected));
+        // even results reject "20 Elo stronger" eventually, and accept "20 Elo weaker"
+        assert_eq!(sprt.verdict(&results(2, 1000)), Some(Verdict::Rejected));
+        let weaker = Sprt {
+            elo0: -40.0,
+            elo1: -20.0,
+            ..sprt
+        };
+        assert_eq!(weaker.verdict(&results(2, 1000)), Some(Verdict::Accepted));
+    }
+}
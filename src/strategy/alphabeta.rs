@@ -1,386 +1,698 @@
 //! Alpha - Beta algorithm.
 use std::fmt;
+use std::sync::Arc;
 
+use super::anytime::{publish_iterative_deepening, DepthSearch, Stop};
+use super::endgame::{Endgame, DEFAULT_THRESHOLD};
+use super::evaluation::{Evaluator, Material};
+use super::score::Score;
+use super::search::{Search, SearchInfo};
+use super::transposition::{put_first, Bound, Entry, TranspositionTable, MIN_TABLE_DEPTH};
 use super::Strategy;
 use crate::configuration::{Configuration, Movement};
-use crate::shmem::AtomicMove;
+use itertools::Either;
 use rayon::prelude::*;
-use std::collections::HashMap;
 
 /// Anytime alpha beta algorithm.
 /// Any time algorithms will compute until a deadline is hit and the process is killed.
 /// They are therefore run in another process and communicate through shared memory.
-/// This function is intended to be called from blobwar_iterative_deepening.
-pub fn alpha_beta_anytime(state: &Configuration) {
-    let mut movement = AtomicMove::connect().expect("failed connecting to shmem");
-    for depth in 1..100 {
-        let chosen_movement = AlphaBeta(depth).compute_next_move(state);
-        movement.store(chosen_movement);
-    }
+/// This function is intended to be called from blobwar_iterative_deepening, reports are stored
+/// in the shared memory segment with given name.
+/// All depths share the same transposition table.
+pub fn alpha_beta_anytime(state: &Configuration, shmem: &str) {
+    publish_iterative_deepening(&mut AlphaBeta::new(1), state, shmem)
+}
+
+/// Width of aspiration windows around the current evaluation.
+const ASPIRATION: i16 = 30;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+/// Search algorithms of the `AlphaBeta` strategy. They all share its transposition table.
+pub enum Variant {
+    /// Sequential alpha - beta.
+    Plain,
+    /// Alpha - beta in a window around the current evaluation, searched again with a full
+    /// window if the value falls outside.
+    Aspiration,
+    /// Alpha - beta trying first the movements best rated by a shallow search.
+    Sorted,
+    /// Principal variation search.
+    Pvs,
+    /// Root movements searched in parallel by alpha - beta with full windows.
+    Par,
+    /// Root movements searched in parallel by principal variation searches with the root window.
+    ParClassic,
+    /// All movements of all nodes searched in parallel, without pruning.
+    ParInfinite,
+    /// Root movements searched in parallel by principal variation searches with full windows.
+    ParPvs,
+    /// Root movements searched in parallel by principal variation searches, stopping on
+    /// beta cutoffs.
+    #[default]
+    ParPvs2,
+    /// Like `ParPvs2` but the movements of the root children are also searched in parallel.
+    ParPvsDoubleDepth,
+    /// Root movements searched in parallel by alpha - beta in an aspiration window.
+    ParAspiration,
+    /// Root movements searched in parallel by alpha - beta, stopping on beta cutoffs.
+    Par2,
+}
+
+impl Variant {
+    /// All variants.
+    pub const ALL: [Variant; 12] = [
+        Variant::Plain,
+        Variant::Aspiration,
+        Variant::Sorted,
+        Variant::Pvs,
+        Variant::Par,
+        Variant::ParClassic,
+        Variant::ParInfinite,
+        Variant::ParPvs,
+        Variant::ParPvs2,
+        Variant::ParPvsDoubleDepth,
+        Variant::ParAspiration,
+        Variant::Par2,
+    ];
 }
 
 /// Alpha - Beta algorithm with given maximum number of recursions.
-pub struct AlphaBeta(pub u8);
+/// Leaves are evaluated by given `Evaluator` (material count by default).
+/// Close to the end of the game the `Endgame` solver takes over.
+pub struct AlphaBeta<E: Evaluator = Material> {
+    depth: u8,
+    evaluator: E,
+    table: Option<Arc<TranspositionTable>>,
+    endgame: Endgame,
+    variant: Variant,
+    info: Option<SearchInfo>,
+}
 
-impl fmt::Display for AlphaBeta {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Alpha - Beta (max level: {})", self.0)
+impl AlphaBeta {
+    /// New alpha - beta strategy with given maximum number of recursions.
+    /// A transposition table is allocated on first use and kept from move to move.
+    pub fn new(depth: u8) -> Self {
+        AlphaBeta {
+            depth,
+            evaluator: Material(),
+            table: None,
+            endgame: Endgame::new(DEFAULT_THRESHOLD),
+            variant: Variant::default(),
+            info: None,
+        }
     }
 }
 
-impl Strategy for AlphaBeta {
-    fn compute_next_move(&mut self, state: &Configuration) -> Option<Movement> {
-        //alphabeta(self.0, -127, 127, *state).0
+impl<E: Evaluator> AlphaBeta<E> {
+    /// Evaluate leaves with given `Evaluator`.
+    pub fn with_evaluator<F: Evaluator>(self, evaluator: F) -> AlphaBeta<F> {
+        AlphaBeta {
+            depth: self.depth,
+            evaluator,
+            table: self.table,
+            endgame: self.endgame,
+            variant: self.variant,
+            info: None,
+        }
+    }
 
-        /*
-        let score_courant = state.value();
-        alphabeta_aspiration(self.0, score_courant - 30, score_courant + 30, *state).0
-        */
-        
-        //alphabeta_sorted(self.0, -127, 127, *state).0
-        
-        //alphabeta_pvs(self.0, -127, 127, *state).0
+    /// Search with given algorithm (`Variant::ParPvs2` by default).
+    pub fn variant(self, variant: Variant) -> Self {
+        AlphaBeta { variant, ..self }
+    }
 
-        //alphabeta_par(self.0, *state)
-        
-        //alphabeta_par_classic_with_score(self.0, -127, 127, *state).0
-        
-        //alphabeta_par2(self.0, -127, 127, *state).0
+    /// Solve the game exactly when less than given number of cells are empty (0 to never
+    /// solve).
+    pub fn endgame(self, threshold: usize) -> Self {
+        AlphaBeta {
+            endgame: Endgame::new(threshold),
+            ..self
+        }
+    }
 
-        //alphabeta_par_aspiration(self.0, *state)
+    /// Use given (possibly shared) transposition table.
+    pub fn with_table(self, table: Arc<TranspositionTable>) -> Self {
+        AlphaBeta {
+            table: Some(table),
+            ..self
+        }
+    }
 
-        //alphabeta_par_pvs(self.0, *state)
-        
-        alphabeta_par_pvs2(self.0, -127, 127, *state).0
+    /// Transposition table used so far (if any), for example to read its hit counters.
+    pub fn table(&self) -> Option<&Arc<TranspositionTable>> {
+        self.table.as_ref()
+    }
+}
 
-        //alphabeta_par_pvs_double_depth_par(self.0, -127, 127, *state).0
+impl<E: Evaluator> fmt::Display for AlphaBeta<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Alpha - Beta (max level: {}, {} evaluation)",
+            self.depth, self.evaluator
+        )
+    }
+}
 
-        //alphabeta_par_infinite(self.0, -127, 127, *state).0
+impl<E: Evaluator> Strategy for AlphaBeta<E> {
+    fn compute_next_move(&mut self, state: &Configuration) -> Option<Movement> {
+        let stop = Stop::default();
+        self.info = if self.endgame.applies(state) {
+            self.endgame
+                .solve(state, self.depth, &self.evaluator, &stop)
+        } else {
+            self.search_depth(state, self.depth, &stop)
+        };
+        self.info.as_ref().and_then(SearchInfo::movement)
+    }
+
+    fn search_info(&self) -> Option<&SearchInfo> {
+        self.info.as_ref()
     }
 }
 
-fn alphabeta(profondeur: u8, mut alpha: i8, beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        (None, -state.value())
-    } else {
-        let mut best_move = None;
-        let mut best_val = -127;
-        
-        for coup in state.movements() {
-            let val = -alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup)).1;
-            if val > best_val {
-                best_val = val;
-                best_move = Some(coup);
-                if best_val > alpha {
-                    alpha = best_val;
-                    if alpha >= beta { // or val ?
-                        break; // Problème pr paralléliser
-                    }
-                }
+impl<E: Evaluator> DepthSearch for AlphaBeta<E> {
+    fn search_depth(
+        &mut self,
+        state: &Configuration,
+        depth: u8,
+        stop: &Stop,
+    ) -> Option<SearchInfo> {
+        if self.endgame.applies(state) {
+            return self.endgame.search(state, depth, &self.evaluator, stop);
+        }
+        let table = self.table.get_or_insert_with(Default::default);
+        let search = Search::new(Some(table), &self.evaluator, stop, depth);
+        let (alpha, beta) = (-Score::INFINITY, Score::INFINITY);
+        let state = *state;
+        let (movement, score) = match self.variant {
+            Variant::Plain => alphabeta(depth, alpha, beta, state, &search),
+            Variant::Aspiration => alphabeta_aspiration(depth, state, &search),
+            Variant::Sorted => alphabeta_sorted(depth, alpha, beta, state, &search),
+            Variant::Pvs => alphabeta_pvs(depth, alpha, beta, state, &search),
+            Variant::Par => alphabeta_par(depth, state, &search),
+            Variant::ParClassic => {
+                alphabeta_par_classic_with_score(depth, alpha, beta, state, &search)
+            }
+            Variant::ParInfinite => alphabeta_par_infinite(depth, state, &search),
+            Variant::ParPvs => alphabeta_par_pvs(depth, state, &search),
+            Variant::ParPvs2 => alphabeta_par_pvs2(depth, alpha, beta, state, &search),
+            Variant::ParPvsDoubleDepth => {
+                alphabeta_par_pvs_double_depth_par(depth, alpha, beta, state, &search)
             }
+            Variant::ParAspiration => alphabeta_par_aspiration(depth, state, &search),
+            Variant::Par2 => alphabeta_par2(depth, alpha, beta, state, &search),
+        };
+        if stop.is_raised() {
+            return None;
         }
-        (best_move, best_val)
+        let pv = search.principal_variation(&state, movement, depth);
+        Some(search.info(depth, score, pv))
     }
 }
 
-fn alphabeta_aspiration(profondeur: u8, mut alpha: i8, beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        (None, -state.value())
+/// Result of a search : best movement (if any) and score.
+pub(super) type Found = (Option<Movement>, Score);
+
+/// Count the node and score it if no movement has to be searched : finished games, leaves
+/// and abandoned searches.
+pub(super) fn settled<E: Evaluator>(
+    profondeur: u8,
+    state: &Configuration,
+    search: &Search<E>,
+) -> Option<Score> {
+    search.node();
+    if state.game_over() {
+        Some(search.final_score(state, profondeur))
+    } else if profondeur == 0 {
+        Some(search.evaluate(state))
+    } else if search.stop.is_raised() {
+        Some(Score::DRAW)
     } else {
-        let mut best_move = None;
-        let mut best_val = -127;
-        let score_courant = state.value();
-        for coup in state.movements() {
-            let val = -alphabeta(profondeur - 1, alpha, beta, state.play(&coup)).1;
-            if val > best_val {
-                best_val = val;
-                best_move = Some(coup);
-                if best_val > alpha {
-                    alpha = best_val;
-                    if alpha >= beta { // or val ?
-                        break; // Problème pr paralléliser
-                    }
-                }
-            }
-        }
-        if best_move.is_none() || best_val >= score_courant + 30 || best_val <= score_courant + 30 {
-            alphabeta(profondeur, -127, 127, state)
-        } else {
-        (best_move, best_val)
+        None
+    }
+}
+
+/// Common start of all searches. Return the result of the node if it is settled, by
+/// `settled`, by a pass of a blocked player (searched with `recurse`) or by the table.
+/// Otherwise return its zobrist key and table entry (close to the leaves the table is not used).
+pub(super) fn enter<'a, E, F>(
+    profondeur: u8,
+    alpha: Score,
+    beta: Score,
+    state: &Configuration<'a>,
+    search: &Search<E>,
+    recurse: F,
+) -> Result<(u64, Option<Entry>), Found>
+where
+    E: Evaluator,
+    F: FnOnce(u8, Score, Score, Configuration<'a>) -> Found,
+{
+    if let Some(score) = settled(profondeur, state, search) {
+        return Err((None, score));
+    }
+    if state.movements().next().is_none() {
+        // blocked : we pass
+        let score = recurse(profondeur - 1, -beta, -alpha, state.skip_play()).1;
+        return Err((None, -score));
+    }
+    if profondeur < MIN_TABLE_DEPTH {
+        return Ok((0, None));
+    }
+    let key = state.zobrist();
+    let entry = search.probe(key, profondeur);
+    match entry.and_then(|entry| entry.cutoff(profondeur, alpha, beta)) {
+        Some(score) => Err((entry.and_then(|entry| entry.movement), score)),
+        None => Ok((key, entry)),
+    }
+}
+
+/// Remember the result of a node searched in given window (not close to the leaves).
+pub(super) fn remember<E: Evaluator>(
+    key: u64,
+    profondeur: u8,
+    (movement, score): Found,
+    alpha: Score,
+    beta: Score,
+    search: &Search<E>,
+) {
+    if profondeur >= MIN_TABLE_DEPTH && !search.stop.is_raised() {
+        search.store(
+            key,
+            profondeur,
+            Entry {
+                depth: profondeur,
+                bound: Bound::classify(score, alpha, beta),
+                score,
+                movement,
+            },
+        );
+    }
+}
+
+/// Movements of a node, starting with the one of its table entry.
+pub(super) fn ordered<'b>(
+    state: &'b Configuration,
+    entry: Option<Entry>,
+) -> impl 'b + Iterator<Item = Movement> {
+    match entry.and_then(|entry| entry.movement) {
+        Some(movement) => {
+            let mut movements = state.movements().collect::<Vec<Movement>>();
+            put_first(&mut movements, Some(movement));
+            Either::Left(movements.into_iter())
         }
+        None => Either::Right(state.movements()),
     }
 }
 
-fn alphabeta_pvs(profondeur: u8, mut alpha: i8, mut beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        (None, -state.value())
-    } else {
-        let mut best_move = None;
-        let mut best_val = - 127;
-        for (i, coup) in state.movements().enumerate() {
-            let mut score;
-            if i == 0 {
-                score = -alphabeta_pvs(profondeur - 1, -beta, -alpha, state.play(&coup)).1;
-            } else {
-                score = -alphabeta_pvs(profondeur - 1, -alpha - 1, -alpha, state.play(&coup)).1;
-                if alpha < score && score < beta {
-                    score = -alphabeta_pvs(profondeur - 1, -beta, -score, state.play(&coup)).1;
-                }
-            }
-            if score > best_val {
-                best_val = score;
-                best_move = Some(coup);
-                if best_val > alpha {
-                    alpha = best_val;
-                    if alpha >= beta {
-                        break;
-                    }
+fn alphabeta<E: Evaluator>(
+    profondeur: u8,
+    mut alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    let recurse = |d, a, b, s| alphabeta(d, a, b, s, search);
+    let (key, entry) = match enter(profondeur, alpha, beta, &state, search, recurse) {
+        Ok(node) => node,
+        Err(found) => return found,
+    };
+    let original_alpha = alpha;
+    let mut best_move = None;
+    let mut best_val = -Score::INFINITY;
+    for coup in ordered(&state, entry) {
+        let val = -alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup), search).1;
+        if search.stop.is_raised() {
+            // scores of interrupted searches are meaningless
+            return (None, Score::DRAW);
+        }
+        if val > best_val {
+            best_val = val;
+            best_move = Some(coup);
+            if best_val > alpha {
+                alpha = best_val;
+                if alpha >= beta {
+                    // or val ?
+                    search.cutoff();
+                    break; // Problème pr paralléliser
                 }
             }
         }
-        (best_move, alpha)
     }
+    let found = (best_move, best_val);
+    remember(key, profondeur, found, original_alpha, beta, search);
+    found
 }
 
-fn alphabeta_par(profondeur: u8, state: Configuration) -> Option<Movement> {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        None
+fn alphabeta_aspiration<E: Evaluator>(
+    profondeur: u8,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    let score_courant = search.evaluate(&state);
+    let (alpha, beta) = (score_courant - ASPIRATION, score_courant + ASPIRATION);
+    let (best_move, best_val) = alphabeta(profondeur, alpha, beta, state, search);
+    if best_move.is_none() || best_val <= alpha || best_val >= beta {
+        // value outside of the window : we only have a bound
+        alphabeta(profondeur, -Score::INFINITY, Score::INFINITY, state, search)
     } else {
-        state.movements()
-                .collect::<Vec<Movement>>()
-                .par_iter()
-                .map(|coup| (coup, -alphabeta(profondeur - 1, -127, 127, state.play(&coup)).1))
-                .max_by_key(|&(_, val)| val)
-                .map(|(res, _)| *res)
+        (best_move, best_val)
     }
 }
 
-fn alphabeta_par_classic_with_score(profondeur: u8, mut alpha: i8, mut beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        (None, -state.value())
-    } else {
-        state.movements()
-        .collect::<Vec<Movement>>()
-        .par_iter()
-        .map(|coup| (Some(*coup), -alphabeta_pvs(profondeur - 1, alpha, beta, state.play(&coup)).1))
-        .max_by_key(|&(_, val)| val)
-        .unwrap_or((None, -state.value()))
+fn alphabeta_pvs<E: Evaluator>(
+    profondeur: u8,
+    mut alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    let recurse = |d, a, b, s| alphabeta_pvs(d, a, b, s, search);
+    let (key, entry) = match enter(profondeur, alpha, beta, &state, search, recurse) {
+        Ok(node) => node,
+        Err(found) => return found,
+    };
+    let original_alpha = alpha;
+    let mut best_move = None;
+    let mut best_val = -Score::INFINITY;
+    for (i, coup) in ordered(&state, entry).enumerate() {
+        let mut score;
+        if i == 0 {
+            score = -alphabeta_pvs(profondeur - 1, -beta, -alpha, state.play(&coup), search).1;
+        } else {
+            score = -alphabeta_pvs(
+                profondeur - 1,
+                -alpha - 1,
+                -alpha,
+                state.play(&coup),
+                search,
+            )
+            .1;
+            if alpha < score && score < beta {
+                score = -alphabeta_pvs(profondeur - 1, -beta, -score, state.play(&coup), search).1;
+            }
+        }
+        if search.stop.is_raised() {
+            // scores of interrupted searches are meaningless
+            return (None, Score::DRAW);
+        }
+        if score > best_val {
+            best_val = score;
+            best_move = Some(coup);
+            if best_val > alpha {
+                alpha = best_val;
+                if alpha >= beta {
+                    search.cutoff();
+                    break;
+                }
+            }
+        }
     }
+    let found = (best_move, alpha);
+    remember(key, profondeur, found, original_alpha, beta, search);
+    found
 }
 
-fn alphabeta_par_infinite(profondeur: u8, mut alpha: i8, mut beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        (None, -state.value())
-    } else {
-        state.movements()
+/// Search all movements in parallel in given window, scoring each resulting configuration
+/// with `child`, and keep the best one.
+fn best_in_parallel<'a, E, F>(
+    profondeur: u8,
+    alpha: Score,
+    beta: Score,
+    state: Configuration<'a>,
+    search: &Search<E>,
+    child: F,
+) -> Found
+where
+    E: Evaluator,
+    F: Fn(Configuration<'a>) -> Score + Sync,
+{
+    let recurse = |_, _, _, s| (None, -child(s));
+    let (key, entry) = match enter(profondeur, alpha, beta, &state, search, recurse) {
+        Ok(node) => node,
+        Err(found) => return found,
+    };
+    let found = ordered(&state, entry)
         .collect::<Vec<Movement>>()
         .par_iter()
-        .map(|coup| (Some(*coup), -alphabeta_par_infinite(profondeur - 1, alpha, beta, state.play(&coup)).1))
+        .map(|coup| (Some(*coup), child(state.play(coup))))
         .max_by_key(|&(_, val)| val)
-        .unwrap_or((None, -state.value()))
-    }
+        .unwrap_or((None, -Score::INFINITY));
+    remember(key, profondeur, found, alpha, beta, search);
+    found
 }
 
-fn alphabeta_par_pvs(profondeur: u8, state: Configuration) -> Option<Movement> {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        None
-    } else {
-        state.movements()
-                .collect::<Vec<Movement>>()
-                .par_iter()
-                .map(|coup| (coup, -alphabeta_pvs(profondeur - 1, -127, 127, state.play(&coup)).1))
-                .max_by_key(|&(_, val)| val)
-                .map(|(res, _)| *res)
-    }
+fn alphabeta_par<E: Evaluator>(profondeur: u8, state: Configuration, search: &Search<E>) -> Found {
+    let (alpha, beta) = (-Score::INFINITY, Score::INFINITY);
+    best_in_parallel(profondeur, alpha, beta, state, search, |child| {
+        -alphabeta(profondeur - 1, alpha, beta, child, search).1
+    })
+}
+
+fn alphabeta_par_classic_with_score<E: Evaluator>(
+    profondeur: u8,
+    alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    best_in_parallel(profondeur, alpha, beta, state, search, |child| {
+        -alphabeta_pvs(profondeur - 1, -beta, -alpha, child, search).1
+    })
+}
+
+fn alphabeta_par_infinite<E: Evaluator>(
+    profondeur: u8,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    let (alpha, beta) = (-Score::INFINITY, Score::INFINITY);
+    best_in_parallel(profondeur, alpha, beta, state, search, |child| {
+        -alphabeta_par_infinite(profondeur - 1, child, search).1
+    })
+}
+
+fn alphabeta_par_pvs<E: Evaluator>(
+    profondeur: u8,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    let (alpha, beta) = (-Score::INFINITY, Score::INFINITY);
+    best_in_parallel(profondeur, alpha, beta, state, search, |child| {
+        -alphabeta_pvs(profondeur - 1, alpha, beta, child, search).1
+    })
 }
 
-fn alphabeta_par_pvs2(profondeur: u8, mut alpha: i8, beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        (None, -state.value())
+fn alphabeta_par_aspiration<E: Evaluator>(
+    profondeur: u8,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    let score_courant = search.evaluate(&state);
+    let (alpha, beta) = (score_courant - ASPIRATION, score_courant + ASPIRATION);
+    let (coup, val) = best_in_parallel(profondeur, alpha, beta, state, search, |child| {
+        -alphabeta(profondeur - 1, -beta, -alpha, child, search).1
+    });
+    if coup.is_none() || val <= alpha || val >= beta {
+        // value outside of the window : we only have a bound
+        alphabeta_par(profondeur, state, search)
     } else {
-        let (coup, val) = state.movements()
-             .collect::<Vec<Movement>>()
-             .par_iter()
-             .try_fold(|| -> (Option<Movement>, i8) { (None, -127 as i8) },
-                       |(best_move, best_val), coup| {
-                let mut alpha2 = alpha;
-                let vala = -alphabeta_pvs(profondeur - 1, -beta, -alpha2, state.play(&coup)).1;
-                let mut tmp_bc = best_move;
-                let mut tmp_bv = best_val;
-                if vala > best_val {
-                    tmp_bv = vala;
-                    tmp_bc = Some(*coup);
-                    if tmp_bv > alpha {
-                        alpha2 = tmp_bv;
-                        if alpha2 >= beta {
-                            return Err((tmp_bc, tmp_bv));
-                        }
-                    }
-                    return Ok((tmp_bc, tmp_bv));
-                } else {
-                    return Ok((best_move, best_val));
-                }
-             })
-             .reduce(|| -> Result<(Option<Movement>, i8), (Option<Movement>, i8)> { Ok((None, 0)) }, |a, b| {
-                 let (coup1, val1) = a.unwrap();
-                 let (coup2, val2) = b.unwrap();
-                 if coup1.is_none() {
-                     return b;
-                 } else if coup2.is_none() {
-                     return a;
-                 }
-                 if val1 > val2 { a } else { b }
-             })
-             .unwrap_or((None, -state.value()));
         (coup, val)
     }
 }
 
-fn alphabeta_par_pvs_double_depth_par(profondeur: u8, mut alpha: i8, beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        (None, -state.value())
-    } else {
-        let (coup, val) = state.movements()
-             .collect::<Vec<Movement>>()
-             .par_iter()
-             .try_fold(|| -> (Option<Movement>, i8) { (None, -127 as i8) },
-                       |(best_move, best_val), coup| {
-                let mut alpha2 = alpha;
-                let vala = -alphabeta_par_classic_with_score(profondeur - 1, -beta, -alpha2, state.play(&coup)).1;
-                let mut tmp_bc = best_move;
-                let mut tmp_bv = best_val;
-                if vala > best_val {
-                    tmp_bv = vala;
-                    tmp_bc = Some(*coup);
-                    if tmp_bv > alpha {
-                        alpha2 = tmp_bv;
-                        if alpha2 >= beta {
-                            return Err((tmp_bc, tmp_bv));
-                        }
+/// Search all movements in parallel in given window, scoring each resulting configuration
+/// with `child` (given the window of the child), and stop as soon as one is above beta.
+fn best_in_parallel_with_cutoffs<'a, E, F>(
+    profondeur: u8,
+    alpha: Score,
+    beta: Score,
+    state: Configuration<'a>,
+    search: &Search<E>,
+    child: F,
+) -> Found
+where
+    E: Evaluator,
+    F: Fn(Configuration<'a>, Score, Score) -> Score + Sync,
+{
+    let recurse = |_, a, b, s| (None, child(s, a, b));
+    let (key, entry) = match enter(profondeur, alpha, beta, &state, search, recurse) {
+        Ok(node) => node,
+        Err(found) => return found,
+    };
+    let found = ordered(&state, entry)
+        .collect::<Vec<Movement>>()
+        .par_iter()
+        .try_fold(
+            || -> Found { (None, -Score::INFINITY) },
+            |(best_move, best_val), coup| {
+                let val = -child(state.play(coup), -beta, -alpha);
+                if val > best_val {
+                    if val > alpha && val >= beta {
+                        search.cutoff();
+                        Err((Some(*coup), val))
+                    } else {
+                        Ok((Some(*coup), val))
                     }
-                    return Ok((tmp_bc, tmp_bv));
                 } else {
-                    return Ok((best_move, best_val));
+                    Ok((best_move, best_val))
                 }
-             })
-             .reduce(|| -> Result<(Option<Movement>, i8), (Option<Movement>, i8)> { Ok((None, 0)) }, |a, b| {
-                 let (coup1, val1) = a.unwrap();
-                 let (coup2, val2) = b.unwrap();
-                 if coup1.is_none() {
-                     return b;
-                 } else if coup2.is_none() {
-                     return a;
-                 }
-                 if val1 > val2 { a } else { b }
-             })
-             .unwrap_or((None, -state.value()));
-        (coup, val)
-    }
+            },
+        )
+        .map(|result| result.unwrap_or_else(|cutoff| cutoff))
+        .reduce(
+            || (None, -Score::INFINITY),
+            |a, b| {
+                if a.0.is_none() || (b.0.is_some() && b.1 >= a.1) {
+                    b
+                } else {
+                    a
+                }
+            },
+        );
+    remember(key, profondeur, found, alpha, beta, search);
+    found
 }
 
-fn alphabeta_par_aspiration(profondeur: u8, state: Configuration) -> Option<Movement> {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        None
-    } else {
-        let score_courant = state.value();
-        let coup = state.movements()
-                .collect::<Vec<Movement>>()
-                .par_iter()
-                .map(|coup| (coup, -alphabeta(profondeur - 1, score_courant - 30, score_courant + 30, state.play(&coup)).1))
-                .max_by_key(|&(_, val)| val)
-                .map(|(res, _)| *res);
-        if coup.is_none() {
-            alphabeta_par(profondeur, state)
-        } else {
-            coup
-        }
-    }
+fn alphabeta_par_pvs2<E: Evaluator>(
+    profondeur: u8,
+    alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    best_in_parallel_with_cutoffs(profondeur, alpha, beta, state, search, |child, a, b| {
+        alphabeta_pvs(profondeur - 1, a, b, child, search).1
+    })
 }
 
-fn alphabeta_par2(profondeur: u8, mut alpha: i8, beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        (None, -state.value())
-    } else {
-        let (coup, val) = state.movements()
-             .collect::<Vec<Movement>>()
-             .par_iter()
-             .try_fold(|| -> (Option<Movement>, i8) { (None, -127 as i8) },
-                       |(best_move, best_val), coup| {
-                let mut alpha2 = alpha;
-                let vala = -alphabeta(profondeur - 1, -beta, -alpha2, state.play(&coup)).1;
-                let mut tmp_bc = best_move;
-                let mut tmp_bv = best_val;
-                if vala > best_val {
-                    tmp_bv = vala;
-                    tmp_bc = Some(*coup);
-                    if tmp_bv > alpha {
-                        alpha2 = tmp_bv;
-                        if alpha2 >= beta {
-                            return Err((tmp_bc, tmp_bv));
-                        }
-                    }
-                    return Ok((tmp_bc, tmp_bv));
-                } else {
-                    return Ok((best_move, best_val));
+fn alphabeta_par_pvs_double_depth_par<E: Evaluator>(
+    profondeur: u8,
+    alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    best_in_parallel_with_cutoffs(profondeur, alpha, beta, state, search, |child, a, b| {
+        alphabeta_par_classic_with_score(profondeur - 1, a, b, child, search).1
+    })
+}
+
+fn alphabeta_par2<E: Evaluator>(
+    profondeur: u8,
+    alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    best_in_parallel_with_cutoffs(profondeur, alpha, beta, state, search, |child, a, b| {
+        alphabeta(profondeur - 1, a, b, child, search).1
+    })
+}
+
+fn alphabeta_sorted<E: Evaluator>(
+    profondeur: u8,
+    mut alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    let recurse = |d, a, b, s| alphabeta(d, a, b, s, search);
+    let (key, entry) = match enter(profondeur, alpha, beta, &state, search, recurse) {
+        Ok(node) => node,
+        Err(found) => return found,
+    };
+    let original_alpha = alpha;
+    let mut best_move = None;
+    let mut best_val = -Score::INFINITY;
+    let mut mouvements_ordonnes = state.movements().collect::<Vec<Movement>>();
+    if profondeur >= 4 {
+        mouvements_ordonnes
+            .sort_by_key(|&coup| -alphabeta(2, alpha, beta, state.play(&coup), search).1);
+    }
+    put_first(
+        &mut mouvements_ordonnes,
+        entry.and_then(|entry| entry.movement),
+    );
+    for coup in mouvements_ordonnes {
+        let val = -alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup), search).1;
+        if search.stop.is_raised() {
+            return (None, Score::DRAW);
+        }
+        if val > best_val {
+            best_val = val;
+            best_move = Some(coup);
+            if best_val > alpha {
+                alpha = best_val;
+                if alpha >= beta {
+                    // or val ?
+                    search.cutoff();
+                    break; // Problème pr paralléliser
                 }
-             })
-             .reduce(|| -> Result<(Option<Movement>, i8), (Option<Movement>, i8)> { Ok((None, 0)) }, |a, b| {
-                 let (coup1, val1) = a.unwrap();
-                 let (coup2, val2) = b.unwrap();
-                 if coup1.is_none() {
-                     return b;
-                 } else if coup2.is_none() {
-                     return a;
-                 }
-                 if val1 > val2 { a } else { b }
-             })
-             .unwrap_or((None, -state.value()));
-        (coup, val)
+            }
+        }
     }
+    let found = (best_move, best_val);
+    remember(key, profondeur, found, original_alpha, beta, search);
+    found
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
 
-fn alphabeta_sorted(profondeur: u8, mut alpha: i8, beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 || state.movements().peekable().peek().is_none() {
-        (None, -state.value())
-    } else {
-        let mut best_move = None;
-        let mut best_val = -127;
-        
-        if profondeur >= 4 {
-            let mut mouvements_ordonnes = state.movements().collect::<Vec<Movement>>();
-            mouvements_ordonnes.sort_by_key(|&coup| -alphabeta(2, alpha, beta, state.play(&coup)).1);
-            for coup in mouvements_ordonnes {
-                let (_, val0) = alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup));
-                let val = -val0;
-                if val > best_val {
-                    best_val = val;
-                    best_move = Some(coup);
-                    if best_val > alpha {
-                        alpha = best_val;
-                        if alpha >= beta { // or val ?
-                            break; // Problème pr paralléliser
-                        }
-                    }
-                }
+    /// Some configurations along a game between the first movements.
+    fn positions(board: &Board) -> Vec<Configuration<'_>> {
+        let mut state = Configuration::new(board);
+        let mut positions = vec![state];
+        for ply in 0..12 {
+            let movement = state.movements().nth(ply % 3);
+            match movement {
+                Some(movement) => state.apply_movement(&movement),
+                None => break,
             }
-            (best_move, best_val)
-        } else {
-            for coup in state.movements() {
-                let (_, val0) = alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup));
-                let val = -val0;
-                if val > best_val {
-                    best_val = val;
-                    best_move = Some(coup);
-                    if best_val > alpha {
-                        alpha = best_val;
-                        if alpha >= beta { // or val ?
-                            break; // Problème pr paralléliser
-                        }
-                    }
+            positions.push(state);
+        }
+        positions
+    }
+
+    #[test]
+    fn variants_agree() {
+        let board = Board::default();
+        for state in positions(&board).iter().step_by(3) {
+            for depth in 1..=3 {
+                let stop = Stop::default();
+                let expected = AlphaBeta::new(depth)
+                    .variant(Variant::Plain)
+                    .search_depth(state, depth, &stop)
+                    .unwrap();
+                for &variant in &Variant::ALL {
+                    let info = AlphaBeta::new(depth)
+                        .variant(variant)
+                        .search_depth(state, depth, &stop)
+                        .unwrap();
+                    assert_eq!(
+                        info.score, expected.score,
+                        "{:?} at depth {}",
+                        variant, depth
+                    );
+                    assert!(state.check_move(&info.movement().unwrap()));
                 }
             }
-            (best_move, best_val)
         }
     }
+
+    #[test]
+    fn table_keeps_results() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut strategy = AlphaBeta::new(4);
+        let first = strategy.search_depth(&state, 4, &Stop::default()).unwrap();
+        let second = strategy.search_depth(&state, 4, &Stop::default()).unwrap();
+        assert_eq!(first.score, second.score);
+        // the root is found in the table : nothing is searched again
+        assert!(second.nodes < first.nodes / 10);
+        assert!(strategy.table().unwrap().hits() > 0);
+    }
 }
@@ -0,0 +1,288 @@
+//! In-process anytime search.
+//! Iterative deepening runs on a separate thread and is cooperatively cancelled : searches
+//! check a `Stop` flag at every node and give up as soon as it is raised.
+use super::score;
+use super::search::SearchInfo;
+use crate::configuration::Configuration;
+use crate::shmem::AtomicMove;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Deepest depth tried by iterative deepening.
+pub const MAX_DEPTH: u8 = 100;
+// finished games are scored by their number of plies, which must stay encodable
+const _: () = assert!(MAX_DEPTH <= score::MAX_PLIES);
+
+#[derive(Default)]
+/// Cancellation flag shared between a running search and whoever controls it.
+pub struct Stop(AtomicBool);
+
+impl Stop {
+    /// Ask the search to stop as soon as possible.
+    pub fn raise(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    /// Were we asked to stop ?
+    pub fn is_raised(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Algorithms searching up to a given depth and able to give up halfway.
+pub trait DepthSearch: Send {
+    /// Search given `Configuration` up to given depth.
+    /// Return what was found or None if `stop` was raised before completion.
+    fn search_depth(&mut self, state: &Configuration, depth: u8, stop: &Stop)
+        -> Option<SearchInfo>;
+}
+
+/// Run iterative deepening with given algorithm on a separate thread during given duration.
+/// Return the report of the last completed depth, with nodes and cutoffs summed over all
+/// completed depths and the total elapsed time.
+/// Depth 1 is always completed, even if it takes longer than the given duration.
+pub fn iterative_deepening<S: DepthSearch + ?Sized>(
+    searcher: &mut S,
+    state: &Configuration,
+    duration: Duration,
+) -> Option<SearchInfo> {
+    let start = Instant::now();
+    let deadline = start + duration;
+    let stop = Stop::default();
+    let (sender, receiver) = channel();
+    thread::scope(|scope| {
+        let stop = &stop;
+        scope.spawn(move || {
+            for depth in 1..=MAX_DEPTH {
+                match searcher.search_depth(state, depth, stop) {
+                    Some(info) => {
+                        // no need to go deeper once the game is solved
+                        let exact = info.exact;
+                        if sender.send(info).is_err() || exact {
+                            break;
+                        }
+                    }
+                    None => break,
+                }
+            }
+        });
+        let mut best = receiver.recv().ok()?;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(remaining) {
+                Ok(info) => {
+                    best = SearchInfo {
+                        nodes: best.nodes + info.nodes,
+                        cutoffs: best.cutoffs + info.cutoffs,
+                        ..info
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    stop.raise();
+                    break;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        best.elapsed = start.elapsed();
+        Some(best)
+    })
+}
+
+/// Sub-process counterpart of `iterative_deepening` : search deeper and deeper until killed,
+/// publishing the report of each completed depth in the shared memory segment with given name.
+/// Nodes and cutoffs are summed over all completed depths.
+pub(crate) fn publish_iterative_deepening<S: DepthSearch + ?Sized>(
+    searcher: &mut S,
+    state: &Configuration,
+    shmem: &str,
+) {
+    let mut shared = AtomicMove::connect(shmem).expect("failed connecting to shmem");
+    let start = Instant::now();
+    let stop = Stop::default();
+    let (mut nodes, mut cutoffs) = (0, 0);
+    for depth in 1..=MAX_DEPTH {
+        if let Some(info) = searcher.search_depth(state, depth, &stop) {
+            nodes += info.nodes;
+            cutoffs += info.cutoffs;
+            shared.store(&SearchInfo {
+                nodes,
+                cutoffs,
+                elapsed: start.elapsed(),
+                ..info
+            });
+            if info.exact {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::configuration::Movement;
+    use crate::strategy::score::Score;
+    use crate::strategy::{alphabeta, evil, AlphaBeta, Evil, MinMax};
+
+    /// Fake search completing each depth after given time, unless stopped.
+    /// Depths at or beyond `exact` are reported as solved.
+    struct Sleepy {
+        per_depth: Duration,
+        exact: u8,
+    }
+
+    impl DepthSearch for Sleepy {
+        fn search_depth(
+            &mut self,
+            _: &Configuration,
+            depth: u8,
+            stop: &Stop,
+        ) -> Option<SearchInfo> {
+            let start = Instant::now();
+            while start.elapsed() < self.per_depth * u32::from(depth) {
+                if stop.is_raised() && depth > 1 {
+                    return None;
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            Some(SearchInfo {
+                depth,
+                score: Score::heuristic(depth as i8),
+                nodes: u64::from(depth),
+                cutoffs: 0,
+                elapsed: Duration::default(),
+                pv: Vec::new(),
+                exact: depth >= self.exact,
+            })
+        }
+    }
+
+    #[test]
+    fn deadline_is_respected() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut searcher = Sleepy {
+            per_depth: Duration::from_millis(20),
+            exact: MAX_DEPTH,
+        };
+        let info = iterative_deepening(&mut searcher, &state, Duration::from_millis(100)).unwrap();
+        // depths 1 to 3 take 120 ms, depth 4 would end at 200 ms
+        assert!(info.depth >= 2 && info.depth < 4, "{}", info.depth);
+        assert!(
+            info.elapsed < Duration::from_millis(180),
+            "{:?}",
+            info.elapsed
+        );
+    }
+
+    #[test]
+    fn stopped_depth_is_dropped() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut searcher = Sleepy {
+            per_depth: Duration::from_millis(50),
+            exact: MAX_DEPTH,
+        };
+        // depths 1 and 2 end at 50 and 150 ms, depth 3 would end at 300 ms
+        let info = iterative_deepening(&mut searcher, &state, Duration::from_millis(220)).unwrap();
+        assert_eq!(info.depth, 2);
+        assert_eq!(info.score, Score::heuristic(2));
+        // nodes of both completed depths
+        assert_eq!(info.nodes, 3);
+    }
+
+    #[test]
+    fn first_depth_is_always_completed() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut searcher = Sleepy {
+            per_depth: Duration::from_millis(50),
+            exact: MAX_DEPTH,
+        };
+        let info = iterative_deepening(&mut searcher, &state, Duration::from_millis(0)).unwrap();
+        assert_eq!(info.depth, 1);
+        assert!(info.elapsed >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn solved_games_are_not_deepened() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut searcher = Sleepy {
+            per_depth: Duration::from_millis(1),
+            exact: 3,
+        };
+        let info = iterative_deepening(&mut searcher, &state, Duration::from_secs(10)).unwrap();
+        assert_eq!(info.depth, 3);
+        assert!(info.exact);
+        assert!(info.elapsed < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn in_process_alpha_beta() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut searcher = AlphaBeta::new(1);
+        let duration = Duration::from_millis(200);
+        let info = iterative_deepening(&mut searcher, &state, duration).unwrap();
+        assert!(state.check_move(&info.movement().unwrap()));
+        assert!(info.depth > 1);
+        // the unfinished depth is abandoned quickly
+        assert!(info.elapsed < duration + Duration::from_millis(100));
+    }
+
+    /// Red in a8 is walled in by blue but the game goes on.
+    const RED_BLOCKED: &str = "rbb.....\nbbb.....\nbbb.....\n........\n\
+                               ........\n........\n........\n........\n";
+    /// Blue in a8 is walled in by red but the game goes on.
+    const BLUE_BLOCKED: &str = "brr.....\nrrr.....\nrrr.....\n........\n\
+                                ........\n........\n........\n........\n";
+
+    /// All searches, with every variant.
+    fn searchers() -> Vec<Box<dyn DepthSearch>> {
+        let mut searchers: Vec<Box<dyn DepthSearch>> = vec![Box::new(MinMax::new(1))];
+        for &variant in &alphabeta::Variant::ALL {
+            searchers.push(Box::new(AlphaBeta::new(1).variant(variant)));
+        }
+        for &variant in &evil::Variant::ALL {
+            searchers.push(Box::new(Evil::new(1).variant(variant)));
+        }
+        searchers
+    }
+
+    #[test]
+    fn blocked_root_passes() {
+        let board: Board = RED_BLOCKED.parse().unwrap();
+        let state = Configuration::new(&board);
+        for searcher in &mut searchers() {
+            for &(depth, expected) in &[(1, -7), (2, -8), (3, -8)] {
+                let info = searcher
+                    .search_depth(&state, depth, &Stop::default())
+                    .unwrap();
+                // not a leaf worth -7 : blue keeps duplicating
+                assert_eq!(info.score, Score::heuristic(expected), "depth {}", depth);
+                assert_eq!(info.movement(), None);
+            }
+        }
+        let info =
+            iterative_deepening(&mut AlphaBeta::new(1), &state, Duration::from_millis(50)).unwrap();
+        assert_eq!(info.movement(), None);
+        assert!(info.depth > 1);
+    }
+
+    #[test]
+    fn blocked_adversary_passes() {
+        let board: Board = BLUE_BLOCKED.parse().unwrap();
+        let state = Configuration::new(&board);
+        for searcher in &mut searchers() {
+            // blue passes and red duplicates a second time
+            let info = searcher.search_depth(&state, 3, &Stop::default()).unwrap();
+            assert_eq!(info.score, Score::heuristic(9));
+            assert!(matches!(info.movement(), Some(Movement::Duplicate(_))));
+        }
+    }
+}
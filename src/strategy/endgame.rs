@@ -0,0 +1,268 @@
+//! Exact endgame solver.
+//! When few cells remain empty we search until the end of the game instead of stopping at a
+//! fixed depth. Blocked players pass and finished games are scored with the final margin.
+//! Every legal movement is searched, duplications first. Jumps do not fill any cell so lines
+//! of jumps may never end : lines which are still running at the horizon are evaluated as
+//! usual and the horizon grows until no line was cut. Only then is the result exact, and
+//! only then is a finished game score reported.
+use super::anytime::{Stop, MAX_DEPTH};
+use super::evaluation::Evaluator;
+use super::score::Score;
+use super::search::{Search, SearchInfo};
+use super::transposition::{put_first, Bound, Entry, TranspositionTable};
+use crate::configuration::{Configuration, Movement};
+use std::cmp::Reverse;
+
+/// Default number of empty cells below which we solve the game (disabled : with jumps
+/// searched, solving is rarely cheaper than the depth limited search).
+pub const DEFAULT_THRESHOLD: usize = 0;
+/// Number of slots of the solver's own transposition table.
+const ENDGAME_SLOTS: usize = 1 << 18;
+/// Depth stored in the table for positions searched without cutting any line.
+const SOLVED_DEPTH: u8 = u8::MAX;
+
+/// Solver with its own transposition table (entries are not comparable with the ones of
+/// depth limited searches).
+pub struct Endgame {
+    threshold: usize,
+    table: Option<TranspositionTable>,
+}
+
+impl Endgame {
+    /// New solver used when strictly less than given number of cells are empty.
+    /// A threshold of 0 disables it.
+    pub fn new(threshold: usize) -> Self {
+        Endgame {
+            threshold,
+            table: None,
+        }
+    }
+
+    /// Number of empty cells below which we solve.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Is given configuration close enough to the end to be solved ?
+    pub fn applies(&self, state: &Configuration) -> bool {
+        state.empty_cells().count() < self.threshold
+    }
+
+    /// Search given configuration to the end of the game, cutting lines longer than the
+    /// number of empty cells plus given depth.
+    /// Return None if `stop` was raised before completion.
+    /// The report is exact if no line was cut. Otherwise finished games are reported as
+    /// evaluations of their final margin.
+    pub fn search<E: Evaluator>(
+        &mut self,
+        state: &Configuration,
+        depth: u8,
+        evaluator: &E,
+        stop: &Stop,
+    ) -> Option<SearchInfo> {
+        self.search_lines(state, depth, evaluator, stop)
+            .map(|(info, _)| info)
+    }
+
+    /// Like `search`, also telling whether some line was cut at the horizon.
+    fn search_lines<E: Evaluator>(
+        &mut self,
+        state: &Configuration,
+        depth: u8,
+        evaluator: &E,
+        stop: &Stop,
+    ) -> Option<(SearchInfo, bool)> {
+        let horizon = (state.empty_cells().count() as u8 + depth).min(MAX_DEPTH);
+        let table = self
+            .table
+            .get_or_insert_with(|| TranspositionTable::with_slots(ENDGAME_SLOTS));
+        let search = Search::new(Some(table), evaluator, stop, horizon);
+        let (movement, score, cut) =
+            solve(horizon, -Score::INFINITY, Score::INFINITY, state, &search);
+        if stop.is_raised() {
+            return None;
+        }
+        let score = if cut && score.is_decided() {
+            // the line may not be forced
+            Score::heuristic(score.value().clamp(i8::MIN.into(), i8::MAX.into()) as i8)
+        } else {
+            score
+        };
+        let pv = search.principal_variation(state, movement, horizon);
+        let mut info = search.info(horizon, score, pv);
+        info.exact = !cut;
+        Some((info, cut))
+    }
+
+    /// Grow the horizon until no line is cut, with at most given depth beyond the number of
+    /// empty cells.
+    pub fn solve<E: Evaluator>(
+        &mut self,
+        state: &Configuration,
+        max_depth: u8,
+        evaluator: &E,
+        stop: &Stop,
+    ) -> Option<SearchInfo> {
+        let mut nodes = 0;
+        let mut cutoffs = 0;
+        let mut info = None;
+        for depth in 1..=max_depth.min(MAX_DEPTH) {
+            let (found, cut) = self.search_lines(state, depth, evaluator, stop)?;
+            nodes += found.nodes;
+            cutoffs += found.cutoffs;
+            info = Some(found);
+            if !cut {
+                break;
+            }
+        }
+        info.map(|info| SearchInfo {
+            nodes,
+            cutoffs,
+            ..info
+        })
+    }
+}
+
+/// All movements, duplications first and then best immediate value first.
+fn endgame_movements(state: &Configuration) -> Vec<Movement> {
+    let mut movements: Vec<Movement> = state.movements().collect();
+    movements.sort_by_key(|movement| {
+        (
+            matches!(movement, Movement::Jump(..)),
+            Reverse(state.play(movement).value()),
+        )
+    });
+    movements
+}
+
+/// Search to the end of the game or to the horizon.
+/// Return best movement, score and whether some line was cut at the horizon.
+fn solve<E: Evaluator>(
+    remaining: u8,
+    mut alpha: Score,
+    beta: Score,
+    state: &Configuration,
+    search: &Search<E>,
+) -> (Option<Movement>, Score, bool) {
+    search.node();
+    if state.game_over() {
+        return (None, search.final_score(state, remaining), false);
+    }
+    if remaining == 0 {
+        return (None, search.evaluate(state), true);
+    }
+    if search.stop.is_raised() {
+        return (None, Score::DRAW, true);
+    }
+    let mut movements = endgame_movements(state);
+    if movements.is_empty() {
+        let passed = state.skip_play();
+        if passed.movements().next().is_none() {
+            // nobody can move : the game is over
+            return (None, search.final_score(state, remaining), false);
+        }
+        // blocked : we pass
+        let (_, score, cut) = solve(remaining - 1, -beta, -alpha, &passed, search);
+        return (None, -score, cut);
+    }
+    let key = state.zobrist();
+    let entry = search.probe(key, remaining);
+    if let Some(entry) = entry {
+        if let Some(score) = entry.cutoff(remaining, alpha, beta) {
+            return (entry.movement, score, entry.depth != SOLVED_DEPTH);
+        }
+    }
+    put_first(&mut movements, entry.and_then(|e| e.movement));
+    let original_alpha = alpha;
+    let mut best_move = None;
+    let mut best_val = -Score::INFINITY;
+    let mut cut = false;
+    for movement in movements {
+        let (_, score, child_cut) =
+            solve(remaining - 1, -beta, -alpha, &state.play(&movement), search);
+        let score = -score;
+        cut |= child_cut;
+        if search.stop.is_raised() {
+            return (None, Score::DRAW, true);
+        }
+        if score > best_val {
+            best_val = score;
+            best_move = Some(movement);
+            if best_val > alpha {
+                alpha = best_val;
+                if alpha >= beta {
+                    search.cutoff();
+                    break;
+                }
+            }
+        }
+    }
+    search.store(
+        key,
+        remaining,
+        Entry {
+            depth: if cut { remaining } else { SOLVED_DEPTH },
+            bound: Bound::classify(alpha, original_alpha, beta),
+            score: alpha,
+            movement: best_move,
+        },
+    );
+    (best_move, alpha, cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::strategy::alphabeta::{AlphaBeta, Variant};
+    use crate::strategy::anytime::DepthSearch;
+    use crate::strategy::evaluation::Material;
+    use crate::strategy::Strategy;
+
+    #[test]
+    fn nobody_can_move() {
+        let board: Board = "rxxOOOOO\nxxxOOOOO\nxxxOOOOO\nOOOOOOOO\n\
+                            OOOOOOOO\nOOOOOxxx\nOOOOOxxx\nOOOOOxxb\n"
+            .parse()
+            .unwrap();
+        let state = Configuration::new(&board);
+        let info = Endgame::new(64)
+            .solve(&state, 8, &Material(), &Stop::default())
+            .unwrap();
+        assert_eq!(info.movement(), None);
+        assert_eq!(info.nodes, 1);
+        assert_eq!(info.score, Score::finished(0, 0));
+        assert!(info.exact);
+    }
+
+    #[test]
+    fn agrees_with_full_search() {
+        // red is better off jumping into e4 than duplicating into a8
+        let board: Board = ".rbbbbbb\nbbbbbbbb\nbbbbbbbb\nbbbbbbbb\n\
+                            bbrb.bbb\nbbbbbbbb\nbbbbbbbb\nbbbbbbbr\n"
+            .parse()
+            .unwrap();
+        let state = Configuration::new(&board);
+        let stop = Stop::default();
+        for depth in 1..=4 {
+            let horizon = 2 + depth;
+            let info = Endgame::new(64)
+                .search(&state, depth, &Material(), &stop)
+                .unwrap();
+            let expected = AlphaBeta::new(horizon)
+                .variant(Variant::Plain)
+                .search_depth(&state, horizon, &stop)
+                .unwrap();
+            assert_eq!(info.movement(), expected.movement());
+            assert_eq!(info.score.value(), expected.score.value());
+            // blue may keep jumping around : lines are cut and nothing is certain
+            assert!(!info.exact);
+            assert!(!info.score.is_decided());
+        }
+        let mut solver = AlphaBeta::new(8).endgame(64);
+        assert_eq!(
+            solver.compute_next_move(&state),
+            Some(Movement::Jump(34, 36))
+        );
+    }
+}
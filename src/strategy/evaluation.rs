@@ -0,0 +1,175 @@
+//! Evaluation functions used at the leaves of the searches.
+use crate::configuration::Configuration;
+use crate::positions::Positions;
+use std::fmt;
+
+/// Largest absolute value an evaluation may return.
+const MAX_EVALUATION: i32 = 100;
+/// Weight of one blob of material difference in evaluators mixing several criteria.
+const MATERIAL_WEIGHT: i32 = 2;
+/// Number of movements worth one point in `Mobility`.
+const MOVEMENTS_PER_POINT: i32 = 4;
+
+/// To be an evaluator you need to estimate how good a position is.
+pub trait Evaluator: fmt::Display + Send + Sync {
+    /// Value of given `Configuration` for the player who should play now (higher is better).
+    fn evaluate(&self, state: &Configuration) -> i8;
+}
+
+fn clamp(value: i32) -> i8 {
+    value.clamp(-MAX_EVALUATION, MAX_EVALUATION) as i8
+}
+
+/// Blobs of the player to move and of his adversary.
+fn sides(state: &Configuration) -> (Positions, Positions) {
+    (
+        state.blobs(state.current_player),
+        state.blobs(!state.current_player),
+    )
+}
+
+#[derive(Default, Clone, Copy)]
+/// Blob count difference. This is the historical evaluation.
+pub struct Material();
+
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "material")
+    }
+}
+
+impl Evaluator for Material {
+    fn evaluate(&self, state: &Configuration) -> i8 {
+        -state.value()
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+/// Material, then difference in number of legal movements.
+pub struct Mobility();
+
+impl fmt::Display for Mobility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mobility")
+    }
+}
+
+impl Evaluator for Mobility {
+    fn evaluate(&self, state: &Configuration) -> i8 {
+        let mine = state.movements().count() as i32;
+        let his = state.skip_play().movements().count() as i32;
+        clamp(MATERIAL_WEIGHT * i32::from(-state.value()) + (mine - his) / MOVEMENTS_PER_POINT)
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+/// Material, minus blobs next to empty cells (those are the ones which can be taken).
+pub struct Frontier();
+
+impl fmt::Display for Frontier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "frontier")
+    }
+}
+
+impl Evaluator for Frontier {
+    fn evaluate(&self, state: &Configuration) -> i8 {
+        let (mine, his) = sides(state);
+        let exposed = state.empty().dilate();
+        let frontier = |blobs: Positions| i32::from(blobs.intersection_with(exposed).len());
+        clamp(MATERIAL_WEIGHT * i32::from(-state.value()) - frontier(mine) + frontier(his))
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+/// Material, plus blobs the adversary cannot take back on his next move :
+/// no empty cell next to them is within his reach.
+pub struct Safety();
+
+impl fmt::Display for Safety {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "safety")
+    }
+}
+
+impl Evaluator for Safety {
+    fn evaluate(&self, state: &Configuration) -> i8 {
+        let (mine, his) = sides(state);
+        let empty = state.empty();
+        let safe = |blobs: Positions, adversary: Positions| {
+            // cells the adversary can move to, then blobs next to them
+            let threatened = empty
+                .intersection_with(adversary.dilate().dilate())
+                .dilate();
+            let mut safe_blobs = blobs;
+            safe_blobs.remove(threatened);
+            i32::from(safe_blobs.len())
+        };
+        clamp(MATERIAL_WEIGHT * i32::from(-state.value()) + safe(mine, his) - safe(his, mine))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    /// Red a8 a7 b8 and blue d8 e8 around c8 and f8, every other cell is a hole.
+    const CLOSE: &str = "rr.bb.xx\nrxxxxxxx\nxxxxxxxx\nxxxxxxxx\n\
+                         xxxxxxxx\nxxxxxxxx\nxxxxxxxx\nxxxxxxxx\n";
+
+    /// Evaluations for red and for blue.
+    fn both<E: Evaluator>(evaluator: E, board: &str) -> (i8, i8) {
+        let board: Board = board.parse().unwrap();
+        let state = Configuration::new(&board);
+        (
+            evaluator.evaluate(&state),
+            evaluator.evaluate(&state.skip_play()),
+        )
+    }
+
+    #[test]
+    fn material() {
+        assert_eq!(both(Material(), CLOSE), (1, -1));
+    }
+
+    #[test]
+    fn mobility() {
+        // red in d5 has 8 duplications and 16 jumps, blue in h1 has 3 and 5
+        let board = "........\n........\n........\n...r....\n\
+                     ........\n........\n........\n.......b\n";
+        assert_eq!(both(Mobility(), board), (4, -4));
+        // 3 movements for red against 4 for blue do not make a point
+        assert_eq!(both(Mobility(), CLOSE), (2, -2));
+    }
+
+    #[test]
+    fn frontier() {
+        // b8 is next to c8, d8 and e8 are next to c8 or f8
+        assert_eq!(both(Frontier(), CLOSE), (3, -3));
+        // same material : only b8, a7 and b7 are exposed against g2
+        let board = "rr.xxxxx\nrr.xxxxx\n...xxxxx\nxxxxxxxx\n\
+                     xxxxxxxx\nxxxxx.xx\nxxxxxxbb\nxxxxxxbb\n";
+        assert_eq!(both(Frontier(), board), (-2, 2));
+    }
+
+    #[test]
+    fn safety() {
+        // b8 and d8 are next to c8 which both players reach : a8, a7 and e8 are safe
+        assert_eq!(both(Safety(), CLOSE), (3, -3));
+    }
+
+    #[test]
+    fn evaluations_are_bounded() {
+        // 62 blobs against 1
+        let board = "r.bbbbbb\nbbbbbbbb\nbbbbbbbb\nbbbbbbbb\n\
+                     bbbbbbbb\nbbbbbbbb\nbbbbbbbb\nbbbbbbbb\n";
+        for &(red, blue) in &[
+            both(Mobility(), board),
+            both(Frontier(), board),
+            both(Safety(), board),
+        ] {
+            assert_eq!((red, blue), (-100, 100));
+        }
+    }
+}
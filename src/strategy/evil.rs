@@ -1,145 +1,415 @@
 //! Alpha - Beta algorithm.
 use std::fmt;
+use std::sync::Arc;
 
+use super::alphabeta::{enter, ordered, remember, settled, Found};
+use super::anytime::{publish_iterative_deepening, DepthSearch, Stop};
+use super::evaluation::{Evaluator, Material};
+use super::score::Score;
+use super::search::{Search, SearchInfo};
+use super::transposition::{put_first, Bound, Entry, TranspositionTable, MIN_TABLE_DEPTH};
 use super::Strategy;
 use crate::configuration::{Configuration, Movement};
-use crate::shmem::AtomicMove;
 
 /// Anytime alpha beta algorithm.
 /// Any time algorithms will compute until a deadline is hit and the process is killed.
 /// They are therefore run in another process and communicate through shared memory.
-/// This function is intended to be called from blobwar_iterative_deepening.
-pub fn alpha_beta_anytime(state: &Configuration) {
-    let mut movement = AtomicMove::connect().expect("failed connecting to shmem");
-    for depth in 1..100 {
-        let chosen_movement = Evil(depth).compute_next_move(state);
-        movement.store(chosen_movement);
-    }
+/// This function is intended to be called from blobwar_iterative_deepening, reports are stored
+/// in the shared memory segment with given name.
+/// All depths share the same transposition table.
+pub fn alpha_beta_anytime(state: &Configuration, shmem: &str) {
+    publish_iterative_deepening(&mut Evil::new(1), state, shmem)
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+/// Search algorithms of the `Evil` strategy. They all share its transposition table.
+pub enum Variant {
+    /// Alpha - beta trying first the movements best rated by a shallow search.
+    #[default]
+    Sorted,
+    /// Plain alpha - beta.
+    Plain,
+    /// Negamax with alpha - beta pruning, choosing the root movement by itself.
+    Negamax,
+    /// Alpha - beta searches of increasing depths.
+    Iterative,
+    /// Alpha - beta cutting off as soon as a movement reaches beta.
+    Par,
+}
+
+impl Variant {
+    /// All variants.
+    pub const ALL: [Variant; 5] = [
+        Variant::Sorted,
+        Variant::Plain,
+        Variant::Negamax,
+        Variant::Iterative,
+        Variant::Par,
+    ];
 }
 
 /// Alpha - Beta algorithm with given maximum number of recursions.
-pub struct Evil(pub u8);
+/// Leaves are evaluated by given `Evaluator` (material count by default).
+pub struct Evil<E: Evaluator = Material> {
+    depth: u8,
+    evaluator: E,
+    table: Option<Arc<TranspositionTable>>,
+    variant: Variant,
+    info: Option<SearchInfo>,
+}
 
-impl fmt::Display for Evil {
+impl Evil {
+    /// New evil strategy with given maximum number of recursions.
+    /// A transposition table is allocated on first use and kept from move to move.
+    pub fn new(depth: u8) -> Self {
+        Evil {
+            depth,
+            evaluator: Material(),
+            table: None,
+            variant: Variant::default(),
+            info: None,
+        }
+    }
+}
+
+impl<E: Evaluator> Evil<E> {
+    /// Evaluate leaves with given `Evaluator`.
+    pub fn with_evaluator<F: Evaluator>(self, evaluator: F) -> Evil<F> {
+        Evil {
+            depth: self.depth,
+            evaluator,
+            table: self.table,
+            variant: self.variant,
+            info: None,
+        }
+    }
+
+    /// Search with given algorithm (`Variant::Sorted` by default).
+    pub fn variant(self, variant: Variant) -> Self {
+        Evil { variant, ..self }
+    }
+
+    /// Use given (possibly shared) transposition table.
+    pub fn with_table(self, table: Arc<TranspositionTable>) -> Self {
+        Evil {
+            table: Some(table),
+            ..self
+        }
+    }
+
+    /// Transposition table used so far (if any), for example to read its hit counters.
+    pub fn table(&self) -> Option<&Arc<TranspositionTable>> {
+        self.table.as_ref()
+    }
+}
+
+impl<E: Evaluator> fmt::Display for Evil<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Evil (max level: {})", self.0)
+        write!(
+            f,
+            "Evil (max level: {}, {} evaluation)",
+            self.depth, self.evaluator
+        )
     }
 }
 
-impl Strategy for Evil {
+impl<E: Evaluator> Strategy for Evil<E> {
     fn compute_next_move(&mut self, state: &Configuration) -> Option<Movement> {
-        alphabeta_sorted(self.0, -127, 127, *state).0
+        self.info = self.search_depth(state, self.depth, &Stop::default());
+        self.info.as_ref().and_then(SearchInfo::movement)
+    }
+
+    fn search_info(&self) -> Option<&SearchInfo> {
+        self.info.as_ref()
     }
 }
 
+impl<E: Evaluator> DepthSearch for Evil<E> {
+    fn search_depth(
+        &mut self,
+        state: &Configuration,
+        depth: u8,
+        stop: &Stop,
+    ) -> Option<SearchInfo> {
+        let table = self.table.get_or_insert_with(Default::default);
+        let search = Search::new(Some(table), &self.evaluator, stop, depth);
+        let (alpha, beta) = (-Score::INFINITY, Score::INFINITY);
+        let state = *state;
+        let (movement, score) = match self.variant {
+            Variant::Sorted => alphabeta_sorted(depth, alpha, beta, state, &search),
+            Variant::Plain => alphabeta(depth, alpha, beta, state, &search),
+            Variant::Negamax => negamax_root(depth, state, &search),
+            Variant::Iterative => alphabeta_iteratif(depth, state, &search),
+            Variant::Par => alphabeta_par(depth, alpha, beta, state, &search),
+        };
+        if stop.is_raised() {
+            return None;
+        }
+        let pv = search.principal_variation(&state, movement, depth);
+        Some(search.info(depth, score, pv))
+    }
+}
 
-fn negamax2(profondeur: u8, mut alpha: i8, beta: i8, state: Configuration) -> (i8, i8, i8) {
-    if profondeur == 0 || state.movements().next().is_none() {
-        (-state.value(), alpha, beta)
+/// Return value, alpha and beta.
+fn negamax2<E: Evaluator>(
+    profondeur: u8,
+    mut alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> (Score, Score, Score) {
+    if let Some(score) = settled(profondeur, &state, search) {
+        return (score, alpha, beta);
+    }
+    if state.movements().next().is_none() {
+        // blocked : we pass
+        let score = negamax2(profondeur - 1, -beta, -alpha, state.skip_play(), search).0;
+        return (-score, alpha, beta);
+    }
+    let use_table = profondeur >= MIN_TABLE_DEPTH;
+    let key = state.zobrist();
+    let entry = if use_table {
+        search.probe(key, profondeur)
     } else {
-        let mut best_val = -127;
-        for coup in state.movements().into_iter() {
-            let val = -negamax2(profondeur - 1, -beta, -alpha, state.play(&coup)).0;
-            if val > best_val {
-                best_val = val;
-                if best_val > alpha {
-                    alpha = best_val;
-                }
-                if alpha >= beta {
-                    break;
-                }
+        None
+    };
+    if let Some(score) = entry.and_then(|entry| entry.cutoff(profondeur, alpha, beta)) {
+        return (score, alpha, beta);
+    }
+    let original_alpha = alpha;
+    let mut movements = state.movements().collect::<Vec<Movement>>();
+    put_first(&mut movements, entry.and_then(|entry| entry.movement));
+    let mut best_move = None;
+    let mut best_val = -Score::INFINITY;
+    for coup in movements {
+        let val = -negamax2(profondeur - 1, -beta, -alpha, state.play(&coup), search).0;
+        if search.stop.is_raised() {
+            return (Score::DRAW, alpha, beta);
+        }
+        if val > best_val {
+            best_val = val;
+            best_move = Some(coup);
+            if best_val > alpha {
+                alpha = best_val;
+            }
+            if alpha >= beta {
+                search.cutoff();
+                break;
             }
         }
-        (best_val, alpha, beta)
     }
+    if use_table {
+        search.store(
+            key,
+            profondeur,
+            Entry {
+                depth: profondeur,
+                bound: Bound::classify(best_val, original_alpha, beta),
+                score: best_val,
+                movement: best_move,
+            },
+        );
+    }
+    (best_val, alpha, beta)
 }
 
-fn alphabeta(profondeur: u8, mut alpha: i8, mut beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 || state.movements().next().is_none() {
-        (None, -state.value())
-    } else {
-        let mut best_move = None;
-        let mut best_val = -127;
-        for coup in state.movements().into_iter() {
-            let (_, val0) = alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup));
-            let val = -val0;
-            if val > best_val {
-                best_val = val;
-                best_move = Some(coup);
-                if best_val > alpha {
-                    alpha = best_val;
-                }
-                if alpha >= beta { // or val ?
-                    break; // Problème pr paralléliser
-                }
-            }
+/// Choose the root movement with `negamax2`, which only returns values.
+fn negamax_root<E: Evaluator>(
+    profondeur: u8,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    if state.movements().next().is_none() || profondeur == 0 {
+        return (
+            None,
+            negamax2(profondeur, -Score::INFINITY, Score::INFINITY, state, search).0,
+        );
+    }
+    let mut alpha = -Score::INFINITY;
+    let mut best_move = None;
+    for coup in state.movements() {
+        let val = -negamax2(
+            profondeur - 1,
+            -Score::INFINITY,
+            -alpha,
+            state.play(&coup),
+            search,
+        )
+        .0;
+        if best_move.is_none() || val > alpha {
+            alpha = val;
+            best_move = Some(coup);
         }
-        return (best_move, best_val);
     }
+    (best_move, alpha)
 }
 
-fn alphabeta_sorted(profondeur: u8, mut alpha: i8, mut beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 {
-        (None, -state.value())
-    } else {
-        let mut best_move = None;
-        let mut best_val = -127;
-        let mut mouvements_ordonnes = state.movements().collect::<Vec<Movement>>();
-        if profondeur >= 4 {
-            mouvements_ordonnes.sort_by_key(|&coup| -alphabeta(2, alpha, beta, state.play(&coup)).1);
+fn alphabeta<E: Evaluator>(
+    profondeur: u8,
+    mut alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    let recurse = |d, a, b, s| alphabeta(d, a, b, s, search);
+    let (key, entry) = match enter(profondeur, alpha, beta, &state, search, recurse) {
+        Ok(node) => node,
+        Err(found) => return found,
+    };
+    let original_alpha = alpha;
+    let mut best_move = None;
+    let mut best_val = -Score::INFINITY;
+    for coup in ordered(&state, entry) {
+        let val = -alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup), search).1;
+        if search.stop.is_raised() {
+            // scores of interrupted searches are meaningless
+            return (None, Score::DRAW);
         }
-        for coup in mouvements_ordonnes.into_iter() {
-            let (_, val0) = alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup));
-            let val = -val0;
-            if val > best_val {
-                best_val = val;
-                best_move = Some(coup);
-                if best_val > alpha {
-                    alpha = best_val;
-                }
-                if val >= beta {
-                    break; // Problème pr paralléliser
-                }
+        if val > best_val {
+            best_val = val;
+            best_move = Some(coup);
+            if best_val > alpha {
+                alpha = best_val;
+            }
+            if alpha >= beta {
+                // or val ?
+                search.cutoff();
+                break; // Problème pr paralléliser
             }
         }
-        return (best_move, best_val);
     }
+    let found = (best_move, best_val);
+    remember(key, profondeur, found, original_alpha, beta, search);
+    found
 }
 
-fn alphabeta_iteratif(profondeur: u8, mut alpha: i8, mut beta: i8, state: Configuration) -> (Option<Movement>, i8) {
+fn alphabeta_sorted<E: Evaluator>(
+    profondeur: u8,
+    mut alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    let recurse = |d, a, b, s| alphabeta(d, a, b, s, search);
+    let (key, entry) = match enter(profondeur, alpha, beta, &state, search, recurse) {
+        Ok(node) => node,
+        Err(found) => return found,
+    };
+    let original_alpha = alpha;
     let mut best_move = None;
-    let mut alpha = -127;
-    for i in 1..(profondeur+1) {
-        let (tmp_coup, tmp_alpha) = alphabeta(i, alpha, -alpha, state);
-        if i8::abs(tmp_alpha) >= i8::abs(alpha) {
-            alpha = -1 * i8::abs(tmp_alpha);
+    let mut best_val = -Score::INFINITY;
+    let mut mouvements_ordonnes = state.movements().collect::<Vec<Movement>>();
+    if profondeur >= 4 {
+        mouvements_ordonnes
+            .sort_by_key(|&coup| -alphabeta(2, alpha, beta, state.play(&coup), search).1);
+    }
+    put_first(
+        &mut mouvements_ordonnes,
+        entry.and_then(|entry| entry.movement),
+    );
+    for coup in mouvements_ordonnes {
+        let val = -alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup), search).1;
+        if search.stop.is_raised() {
+            return (None, Score::DRAW);
+        }
+        if val > best_val {
+            best_val = val;
+            best_move = Some(coup);
+            if best_val > alpha {
+                alpha = best_val;
+            }
+            if val >= beta {
+                search.cutoff();
+                break; // Problème pr paralléliser
+            }
         }
-        best_move = tmp_coup;
     }
-    (best_move, alpha)
+    let found = (best_move, best_val);
+    remember(key, profondeur, found, original_alpha, beta, search);
+    found
+}
 
+fn alphabeta_iteratif<E: Evaluator>(
+    profondeur: u8,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    // shallow searches fill the table, which then orders the movements of deeper ones
+    let mut best = (None, -Score::INFINITY);
+    for i in 1..=profondeur {
+        best = alphabeta(i, -Score::INFINITY, Score::INFINITY, state, search);
+    }
+    best
 }
 
-fn alphabeta_par(profondeur: u8, mut alpha: i8, mut beta: i8, state: Configuration) -> (Option<Movement>, i8) {
-    if profondeur == 0 {
-        (None, -state.value())
-    } else {
-        let mut best_move = None;
-        let mut best_val = -127;
-        for coup in state.movements().into_iter() {
-            let (_, val0) = alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup));
-            let val = -val0;
-            if val > best_val {
-                best_val = val;
-                best_move = Some(coup);
-                if best_val > alpha {
-                    alpha = best_val;
-                }
-                if val >= beta {
-                    break; // Problème pr paralléliser
+fn alphabeta_par<E: Evaluator>(
+    profondeur: u8,
+    mut alpha: Score,
+    beta: Score,
+    state: Configuration,
+    search: &Search<E>,
+) -> Found {
+    let recurse = |d, a, b, s| alphabeta(d, a, b, s, search);
+    let (key, entry) = match enter(profondeur, alpha, beta, &state, search, recurse) {
+        Ok(node) => node,
+        Err(found) => return found,
+    };
+    let original_alpha = alpha;
+    let mut best_move = None;
+    let mut best_val = -Score::INFINITY;
+    for coup in ordered(&state, entry) {
+        let val = -alphabeta(profondeur - 1, -beta, -alpha, state.play(&coup), search).1;
+        if search.stop.is_raised() {
+            return (None, Score::DRAW);
+        }
+        if val > best_val {
+            best_val = val;
+            best_move = Some(coup);
+            if best_val > alpha {
+                alpha = best_val;
+            }
+            if val >= beta {
+                search.cutoff();
+                break; // Problème pr paralléliser
+            }
+        }
+    }
+    let found = (best_move, best_val);
+    remember(key, profondeur, found, original_alpha, beta, search);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::strategy::AlphaBeta;
+
+    #[test]
+    fn variants_agree_with_alpha_beta() {
+        let board = Board::default();
+        let mut state = Configuration::new(&board);
+        for ply in 0..6 {
+            for depth in 1..=3 {
+                let stop = Stop::default();
+                let expected = AlphaBeta::new(depth)
+                    .search_depth(&state, depth, &stop)
+                    .unwrap();
+                for &variant in &Variant::ALL {
+                    let info = Evil::new(depth)
+                        .variant(variant)
+                        .search_depth(&state, depth, &stop)
+                        .unwrap();
+                    assert_eq!(
+                        info.score, expected.score,
+                        "{:?} at depth {}",
+                        variant, depth
+                    );
+                    assert!(state.check_move(&info.movement().unwrap()));
                 }
             }
+            let movement = state.movements().nth(ply % 2).unwrap();
+            state.apply_movement(&movement);
         }
-        return (best_move, best_val);
     }
-}
\ No newline at end of file
+}
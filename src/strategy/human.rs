@@ -1,68 +1,127 @@
 //! Human player.
 use super::Strategy;
 use crate::configuration::{Configuration, Movement};
-use crate::positions::{BoardPosition, Position};
 use std::fmt;
 use std::io;
 use std::io::BufRead;
 
 /// Let a human enter moves on stdin.
-pub struct Human();
+/// Entering "undo" takes back our previous movement (and the answer of the adversary).
+/// Once the input is closed the human passes.
+#[derive(Default)]
+pub struct Human {
+    undo: bool,
+    /// Where movements are read (stdin if None).
+    input: Option<Box<dyn BufRead>>,
+}
+
+impl Human {
+    /// New human player.
+    pub fn new() -> Self {
+        Human::default()
+    }
+
+    /// Human player reading its movements from given input instead of stdin.
+    pub fn with_input<R: BufRead + 'static>(input: R) -> Self {
+        Human {
+            undo: false,
+            input: Some(Box::new(input)),
+        }
+    }
+
+    /// Read a line, returning false at the end of the input.
+    fn read_line(&mut self, line: &mut String) -> Result<bool, io::Error> {
+        let read = match self.input {
+            Some(ref mut input) => input.read_line(line)?,
+            None => io::stdin().lock().read_line(line)?,
+        };
+        Ok(read > 0)
+    }
+
+    /// Read a movement in Ataxx notation or "undo".
+    fn ask_move(&mut self) -> Result<Answer, io::Error> {
+        println!(
+            "enter movement (like c3 to duplicate to c3 or a1c3 to jump from a1 to c3) or undo"
+        );
+        let mut line = String::new();
+        if !self.read_line(&mut line)? {
+            println!("no more input : passing");
+            return Ok(Answer::Pass);
+        }
+        if line.trim() == "undo" {
+            return Ok(Answer::Undo);
+        }
+        line.parse().map(Answer::Play).map_err(|e| {
+            println!("{}", e);
+            io::Error::new(io::ErrorKind::InvalidInput, e)
+        })
+    }
+}
+
 impl fmt::Display for Human {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Human")
     }
 }
 
-fn ask_cell() -> Result<(u8, u8), io::Error> {
-    let mut line = String::new();
-    let stdin = io::stdin();
-    stdin.lock().read_line(&mut line)?;
-    let mut words = line.split_whitespace();
-    let y: u8 = words
-        .next()
-        .unwrap()
-        .parse()
-        .map_err(|_| io::ErrorKind::Other)?;
-    let x: u8 = words
-        .next()
-        .unwrap()
-        .parse()
-        .map_err(|_| io::ErrorKind::Other)?;
-    Ok((x, y))
-}
-
-fn ask_move() -> Result<Movement, io::Error> {
-    println!("enter start point");
-    let (sx, sy) = ask_cell()?;
-    let start_position = Position::from_2d(sx, sy);
-    println!("enter end point");
-    let (ex, ey) = ask_cell()?;
-    let end_position = Position::from_2d(ex, ey);
-    match start_position.distance_to(end_position) {
-        1 => Ok(Movement::Duplicate(end_position)),
-        2 => Ok(Movement::Jump(start_position, end_position)),
-        _ => {
-            println!("invalid movement");
-            ask_move()
-        }
-    }
+/// What the human wants to do.
+enum Answer {
+    Play(Movement),
+    Undo,
+    /// Nothing more to read.
+    Pass,
 }
 
 impl Strategy for Human {
     fn compute_next_move(&mut self, configuration: &Configuration) -> Option<Movement> {
+        self.undo = false;
         if configuration.movements().next().is_some() {
             loop {
-                if let Ok(movement) = ask_move() {
-                    if configuration.check_move(&movement) {
-                        return Some(movement);
-                    } else {
-                        println!("invalid movement (are you playing your color ?)");
+                match self.ask_move() {
+                    Ok(Answer::Play(movement)) if configuration.check_move(&movement) => {
+                        return Some(movement)
+                    }
+                    Ok(Answer::Play(_)) => {
+                        println!("invalid movement (are you playing your color ?)")
+                    }
+                    Ok(Answer::Undo) => {
+                        self.undo = true;
+                        return None;
                     }
+                    Ok(Answer::Pass) => return None,
+                    Err(_) => {}
                 }
             }
         } else {
             None
         }
     }
+
+    fn wants_undo(&self) -> bool {
+        self.undo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::configuration::{Configuration, Verbosity};
+    use crate::strategy::Greedy;
+
+    #[test]
+    fn end_of_input_passes() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let movement = state.movements().next().unwrap();
+        let mut human = Human::with_input(io::Cursor::new(format!("{}\n", movement)));
+        assert_eq!(human.compute_next_move(&state), Some(movement));
+        assert_eq!(human.compute_next_move(&state), None);
+        assert!(!human.wants_undo());
+        // a closed input ends the game instead of aborting it
+        let mut state = Configuration::new(&board);
+        let outcome = state.battle_with(Human::with_input(io::empty()), Greedy(), Verbosity::Quiet);
+        assert!(state.game_over());
+        assert!(outcome.movements.iter().step_by(2).all(Option::is_none));
+    }
 }
@@ -1,14 +1,52 @@
 //! Generic iterative deepening strategies (with variable algorithms).
+//!
+//! Searches can run in the blobwar_iterative_deepening binary, either started for each move or
+//! started once as a worker serving a whole game. Workers read one JSON `SearchRequest` per
+//! line on their standard input and answer the JSON `SearchInfo` of the search (null if
+//! nothing was found) on one line of their standard output.
+use std::env;
+use std::error::Error;
 use std::fmt;
-
-use std::io;
-use std::process::Command;
-use std::thread::sleep;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread::{self, sleep};
 use std::time::Duration;
 
-use super::Strategy;
+use super::anytime::{iterative_deepening, DepthSearch};
+use super::search::SearchInfo;
+use super::{AlphaBeta, Evil, Greedy, MinMax, Strategy};
+use crate::board::Board;
 use crate::configuration::{Configuration, Movement};
 use crate::shmem::AtomicMove;
+use serde_derive::{Deserialize, Serialize};
+
+/// Name of the binary running sub-process searches.
+pub const HELPER_NAME: &str = "blobwar_iterative_deepening";
+
+/// Environment variable giving the path of the `HELPER_NAME` binary.
+pub const HELPER_VARIABLE: &str = "BLOBWAR_ITERATIVE_DEEPENING";
+
+/// Time given to workers on top of the search duration to answer (depth 1 is always
+/// completed, even when it takes longer than the duration).
+const WORKER_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+/// The binary running sub-process searches is at none of these paths.
+pub struct HelperNotFound(pub Vec<PathBuf>);
+
+impl fmt::Display for HelperNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} not found, tried", HELPER_NAME)?;
+        for path in &self.0 {
+            write!(f, " {}", path.display())?;
+        }
+        write!(f, " (set {} to its path)", HELPER_VARIABLE)
+    }
+}
+
+impl Error for HelperNotFound {}
 
 #[derive(Copy, Clone, Debug)]
 /// All possible strategies for iterative deepening.
@@ -17,59 +55,482 @@ pub enum IterativeStrategy {
     MinMax,
     /// AlphaBeta algorithm
     AlphaBeta,
+    /// Evil algorithm
+    Evil,
 }
 
-/// Anytime algorithms strategies. Implemented in another process.
+impl IterativeStrategy {
+    /// Strategy with given index (its rank in the enum), as given to
+    /// blobwar_iterative_deepening.
+    pub fn from_index(index: usize) -> Option<Self> {
+        [
+            IterativeStrategy::MinMax,
+            IterativeStrategy::AlphaBeta,
+            IterativeStrategy::Evil,
+        ]
+        .get(index)
+        .cloned()
+    }
+
+    /// Build the algorithm for in-process searches.
+    fn searcher(self) -> Box<dyn DepthSearch> {
+        match self {
+            IterativeStrategy::MinMax => Box::new(MinMax::new(1)),
+            IterativeStrategy::AlphaBeta => Box::new(AlphaBeta::new(1)),
+            IterativeStrategy::Evil => Box::new(Evil::new(1)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+/// Search asked to a worker.
+struct SearchRequest {
+    /// Serialized `Configuration` to search.
+    configuration: String,
+    /// Time to search in milliseconds.
+    duration: u64,
+}
+
+/// Search given request line with given algorithm.
+fn answer(searcher: &mut dyn DepthSearch, line: &str) -> Result<Option<SearchInfo>, String> {
+    let request: SearchRequest = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let board = Board::try_deserialize(&request.configuration).map_err(|e| e.to_string())?;
+    let state = Configuration::try_deserialize(&request.configuration, &board)
+        .map_err(|e| e.to_string())?;
+    Ok(iterative_deepening(
+        searcher,
+        &state,
+        Duration::from_millis(request.duration),
+    ))
+}
+
+/// Worker loop of blobwar_iterative_deepening : answer the searches requested on given input
+/// until it is closed. The algorithm, and therefore its transposition table, is kept from
+/// search to search. Invalid requests are reported on stderr and answered with null.
+pub fn serve<R: BufRead, W: Write>(
+    strategy: IterativeStrategy,
+    input: R,
+    mut output: W,
+) -> io::Result<()> {
+    let mut searcher = strategy.searcher();
+    for line in input.lines() {
+        let info = answer(searcher.as_mut(), &line?).unwrap_or_else(|e| {
+            eprintln!("invalid search request: {}", e);
+            None
+        });
+        writeln!(output, "{}", serde_json::to_string(&info)?)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// Running blobwar_iterative_deepening worker. It is killed when dropped.
+/// Its answers are read on a separate thread so that we can stop waiting for them.
+struct Worker {
+    child: Child,
+    input: ChildStdin,
+    answers: Receiver<io::Result<String>>,
+}
+
+impl Worker {
+    /// Start the worker binary at given path with given algorithm.
+    fn start(helper: &Path, strategy: IterativeStrategy) -> io::Result<Self> {
+        let mut child = Command::new(helper)
+            .arg("--worker")
+            .arg((strategy as usize).to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input = child.stdin.take().expect("missing worker input");
+        let output = BufReader::new(child.stdout.take().expect("missing worker output"));
+        let (sender, answers) = channel();
+        // ends when the worker exits and closes its output
+        thread::spawn(move || {
+            for line in output.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Worker {
+            child,
+            input,
+            answers,
+        })
+    }
+
+    /// Search given configuration during given time (in milliseconds).
+    /// Fail if the worker does not answer in time.
+    fn search(&mut self, state: &Configuration, duration: u64) -> io::Result<Option<SearchInfo>> {
+        let request = SearchRequest {
+            configuration: state.serialize(),
+            duration,
+        };
+        writeln!(self.input, "{}", serde_json::to_string(&request)?)?;
+        self.input.flush()?;
+        let deadline = Duration::from_millis(duration) + WORKER_GRACE;
+        let line = match self.answers.recv_timeout(deadline) {
+            Ok(line) => line?,
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "worker did not answer in time",
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "worker exited",
+                ))
+            }
+        };
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // it may have exited already
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Where searches run.
+enum Mode {
+    /// On a thread of this process, with the algorithm kept from move to move.
+    InProcess(Box<dyn DepthSearch>),
+    /// In a new blobwar_iterative_deepening process for each move.
+    SubProcess,
+    /// In a blobwar_iterative_deepening worker serving all moves (started on first use and
+    /// restarted if it fails).
+    Worker(Option<Worker>),
+}
+
+/// Anytime algorithms strategies.
+/// By default the search runs on a thread of this process and is cancelled at the deadline.
+/// It can also run in another process which is killed at the deadline, or in a worker process
+/// kept for the whole game. If the other process fails or does not complete depth 1 in time,
+/// the `Greedy` movement is played instead.
 pub struct IterativeDeepening {
     strategy: IterativeStrategy,
     duration: u64,
+    mode: Mode,
+    /// Path of the binary running sub-process or worker searches, if given.
+    helper: Option<PathBuf>,
+    /// Report of the last search (as published by the sub-process for sub-process ones).
+    info: Option<SearchInfo>,
 }
 
 impl fmt::Display for IterativeDeepening {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{:?} (iterative deepening {}ms)",
-            self.strategy, self.duration
+            "{:?} (iterative deepening {}ms{})",
+            self.strategy,
+            self.duration,
+            match self.mode {
+                Mode::InProcess(_) => "",
+                Mode::SubProcess => " in sub-process",
+                Mode::Worker(_) => " in worker process",
+            }
         )
     }
 }
 
 impl Strategy for IterativeDeepening {
     fn compute_next_move(&mut self, state: &Configuration) -> Option<Movement> {
-        let movement = AtomicMove::new().expect("failed creating shmem");
+        if let Mode::InProcess(ref mut searcher) = self.mode {
+            let duration = Duration::from_millis(self.duration);
+            self.info = iterative_deepening(searcher.as_mut(), state, duration);
+            return self.info.as_ref().and_then(SearchInfo::movement);
+        }
+        self.info = None;
+        // nothing to search when we can only pass
+        state.movements().next()?;
+        let info = match self.mode {
+            Mode::Worker(_) => self.compute_in_worker(state),
+            _ => self.compute_in_subprocess(state),
+        };
+        match info.as_ref().and_then(SearchInfo::movement) {
+            Some(movement) if state.check_move(&movement) => {
+                self.info = info;
+                Some(movement)
+            }
+            movement => {
+                match movement {
+                    Some(movement) => eprintln!(
+                        "{}: search process returned illegal movement {}",
+                        self, movement
+                    ),
+                    None => eprintln!("{}: search process found no movement", self),
+                }
+                eprintln!("{}: playing greedy movement instead", self);
+                Greedy().compute_next_move(state)
+            }
+        }
+    }
+
+    fn search_info(&self) -> Option<&SearchInfo> {
+        self.info.as_ref()
+    }
+}
+
+impl IterativeDeepening {
+    /// Run the anytime algorithm in the blobwar_iterative_deepening binary and kill it at the
+    /// deadline. Return the report of the last depth it completed.
+    /// Failures are reported on stderr.
+    fn compute_in_subprocess(&self, state: &Configuration) -> Option<SearchInfo> {
+        let movement = AtomicMove::new()
+            .map_err(|e| eprintln!("{}: failed creating shared memory: {}", self, e))
+            .ok()?;
+        let helper = self
+            .helper_path()
+            .map_err(|e| eprintln!("{}: {}", self, e))
+            .ok()?;
         let configuration = state.serialize();
-        let mut child = Command::new("blobwar_iterative_deepening")
-            .current_dir("./target/release/")
+        let mut child = Command::new(helper)
             .arg(configuration)
             .arg((self.strategy as usize).to_string())
+            .arg(movement.name())
             .spawn()
-            .expect("failed to start child process");
+            .map_err(|e| eprintln!("{}: failed to start search process: {}", self, e))
+            .ok()?;
         sleep(Duration::from_millis(self.duration));
-        if let Err(e) = child.kill() {
-            if e.kind() != io::ErrorKind::InvalidInput {
-                panic!("failed to kill child")
+        match child.try_wait() {
+            // the search ended by itself (it solved the game) or crashed
+            Ok(Some(status)) if !status.success() => {
+                eprintln!("{}: search process failed ({})", self, status)
+            }
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                if let Err(e) = child.kill().and_then(|_| child.wait()) {
+                    eprintln!("{}: failed to kill search process: {}", self, e)
+                }
             }
+            Err(e) => eprintln!("{}: failed to check search process: {}", self, e),
         }
         movement.load()
     }
-}
 
-impl IterativeDeepening {
-    /// New iterative deepening strategy with given algorithm.
+    /// Search in the worker, starting it if needed. Return the report of the search.
+    /// Failures are reported on stderr and the failed worker (possibly stuck past the
+    /// deadline) is killed, to be restarted on next move.
+    fn compute_in_worker(&mut self, state: &Configuration) -> Option<SearchInfo> {
+        let worker = match self.mode {
+            Mode::Worker(ref mut worker) => worker.take(),
+            _ => None,
+        };
+        let mut worker = match worker {
+            Some(worker) => worker,
+            None => {
+                let helper = self
+                    .helper_path()
+                    .map_err(|e| eprintln!("{}: {}", self, e))
+                    .ok()?;
+                Worker::start(&helper, self.strategy)
+                    .map_err(|e| eprintln!("{}: failed to start worker: {}", self, e))
+                    .ok()?
+            }
+        };
+        match worker.search(state, self.duration) {
+            Ok(info) => {
+                self.mode = Mode::Worker(Some(worker));
+                info
+            }
+            Err(e) => {
+                eprintln!("{}: worker failed: {}", self, e);
+                None
+            }
+        }
+    }
+
+    /// New iterative deepening strategy with given algorithm, searching in-process.
     /// default time is 2 seconds.
     pub fn new(strategy: IterativeStrategy) -> IterativeDeepening {
         IterativeDeepening {
             strategy,
             duration: 2000,
+            mode: Mode::InProcess(strategy.searcher()),
+            helper: None,
+            info: None,
         }
     }
 
     /// Sets duration in milliseconds on given algorithm.
-    pub fn duration(&self, duration: u64) -> Self {
+    pub fn duration(self, duration: u64) -> Self {
+        IterativeDeepening { duration, ..self }
+    }
+
+    /// Search in the blobwar_iterative_deepening binary instead of in-process.
+    pub fn in_subprocess(self) -> Self {
         IterativeDeepening {
-            strategy: self.strategy,
-            duration,
+            mode: Mode::SubProcess,
+            ..self
+        }
+    }
+
+    /// Search in a blobwar_iterative_deepening worker kept for all moves, so that its
+    /// transposition table stays warm.
+    pub fn in_worker(self) -> Self {
+        IterativeDeepening {
+            mode: Mode::Worker(None),
+            ..self
+        }
+    }
+
+    /// Run sub-process searches with the binary at given path.
+    pub fn helper<P: Into<PathBuf>>(self, path: P) -> Self {
+        IterativeDeepening {
+            helper: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Path of the binary running sub-process searches : the one given with `helper`, else
+    /// the one in the `BLOBWAR_ITERATIVE_DEEPENING` environment variable, else the one next
+    /// to the running executable (or in its parent directory, for tests and examples built
+    /// by cargo).
+    pub fn helper_path(&self) -> Result<PathBuf, HelperNotFound> {
+        let candidates = match self
+            .helper
+            .clone()
+            .or_else(|| env::var_os(HELPER_VARIABLE).map(PathBuf::from))
+        {
+            Some(path) => vec![path],
+            None => {
+                let executable = format!("{}{}", HELPER_NAME, env::consts::EXE_SUFFIX);
+                env::current_exe()
+                    .ok()
+                    .iter()
+                    .flat_map(|path| path.ancestors().skip(1).take(2))
+                    .map(|directory| directory.join(&executable))
+                    .collect()
+            }
+        };
+        candidates
+            .iter()
+            .find(|path| path.is_file())
+            .cloned()
+            .ok_or(HelperNotFound(candidates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Instant;
+
+    #[test]
+    fn serve_requests() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let request = SearchRequest {
+            configuration: state.serialize(),
+            duration: 50,
+        };
+        let input = format!(
+            "{}\nnot a request\n{{\"configuration\":\"0r\",\"duration\":5}}\n",
+            serde_json::to_string(&request).unwrap()
+        );
+        let mut output = Vec::new();
+        serve(
+            IterativeStrategy::AlphaBeta,
+            Cursor::new(input),
+            &mut output,
+        )
+        .unwrap();
+        let answers: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+        assert_eq!(answers.len(), 3);
+        let info: Option<SearchInfo> = serde_json::from_str(answers[0]).unwrap();
+        let movement = info.and_then(|info| info.movement()).unwrap();
+        assert!(state.check_move(&movement));
+        assert_eq!(&answers[1..], &["null", "null"]);
+    }
+
+    #[test]
+    fn stuck_workers_are_replaced() {
+        let directory = env::temp_dir().join(format!("blobwar-stuck-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let helper = directory.join("stuck");
+        fs::write(&helper, "#!/bin/sh\nexec sleep 60\n").unwrap();
+        fs::set_permissions(&helper, fs::Permissions::from_mode(0o755)).unwrap();
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let mut strategy = IterativeDeepening::new(IterativeStrategy::AlphaBeta)
+            .duration(10)
+            .in_worker()
+            .helper(&helper);
+        for _ in 0..2 {
+            let start = Instant::now();
+            let movement = strategy.compute_next_move(&state).unwrap();
+            assert!(start.elapsed() < WORKER_GRACE * 3);
+            assert!(state.check_move(&movement));
+            // greedy fallback
+            assert!(strategy.search_info().is_none());
+            assert!(matches!(strategy.mode, Mode::Worker(None)));
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn failing_helpers_fall_back_to_greedy() {
+        let directory = env::temp_dir().join(format!("blobwar-failing-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let helper = directory.join("failing");
+        fs::write(&helper, "#!/bin/sh\nexit 1\n").unwrap();
+        fs::set_permissions(&helper, fs::Permissions::from_mode(0o755)).unwrap();
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let strategy = || {
+            IterativeDeepening::new(IterativeStrategy::AlphaBeta)
+                .duration(100)
+                .helper(&helper)
+        };
+        for mut strategy in vec![strategy().in_subprocess(), strategy().in_worker()] {
+            let movement = strategy.compute_next_move(&state).unwrap();
+            assert_eq!(Some(movement), Greedy().compute_next_move(&state));
+            assert!(strategy.search_info().is_none());
+            // the exited worker is not kept
+            assert!(!matches!(strategy.mode, Mode::Worker(Some(_))));
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn helper_path_lookup() {
+        let directory = env::temp_dir().join(format!("blobwar-helper-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let helper = directory.join(HELPER_NAME);
+        fs::write(&helper, "").unwrap();
+        let strategy = IterativeDeepening::new(IterativeStrategy::AlphaBeta);
+        // the environment variable
+        env::set_var(HELPER_VARIABLE, &helper);
+        assert_eq!(strategy.helper_path().unwrap(), helper);
+        let missing = directory.join("missing");
+        env::set_var(HELPER_VARIABLE, &missing);
+        let error = strategy.helper_path().unwrap_err();
+        assert_eq!(error.0, vec![missing.clone()]);
+        assert!(error.to_string().contains(HELPER_VARIABLE));
+        // the builder option comes first
+        let given = IterativeDeepening::new(IterativeStrategy::AlphaBeta).helper(&helper);
+        assert_eq!(given.helper_path().unwrap(), helper);
+        // next to the test executable, which is in target/debug/deps
+        env::remove_var(HELPER_VARIABLE);
+        let executable = env::current_exe().unwrap();
+        let expected: Vec<PathBuf> = executable
+            .ancestors()
+            .skip(1)
+            .take(2)
+            .map(|directory| directory.join(HELPER_NAME))
+            .collect();
+        match strategy.helper_path() {
+            Ok(path) => assert!(expected.contains(&path), "{}", path.display()),
+            Err(HelperNotFound(candidates)) => assert_eq!(candidates, expected),
         }
+        fs::remove_dir_all(&directory).unwrap();
     }
 }
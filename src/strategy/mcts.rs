@@ -0,0 +1,440 @@
+//! Monte Carlo Tree Search with UCT selection.
+//! Each iteration walks down the tree choosing children by their upper confidence bound,
+//! expands the reached leaf, finishes the game with a playout and propagates the result back.
+use super::score::Score;
+use super::search::SearchInfo;
+use super::Strategy;
+use crate::configuration::{Configuration, Movement};
+use rayon::prelude::*;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+/// Default number of iterations per move.
+const DEFAULT_ITERATIONS: u32 = 10_000;
+/// Default exploration constant of the UCT formula.
+const DEFAULT_EXPLORATION: f64 = 1.4;
+/// Probability for greedy playouts to take the best immediate move instead of a random one.
+const GREEDY_PROBABILITY: f64 = 0.75;
+/// Playouts which did not end after this many plies are decided on material.
+const MAX_PLAYOUT_PLIES: usize = 200;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// How games are finished from the leaves of the tree.
+pub enum Playout {
+    /// Uniformly random movements.
+    Random,
+    /// Mostly the movement with the best immediate value, sometimes a random one.
+    Greedy,
+}
+
+#[derive(Copy, Clone, Debug)]
+/// When to stop searching.
+enum Budget {
+    /// Fixed number of iterations (split between the trees).
+    Iterations(u32),
+    /// Fixed time.
+    Time(Duration),
+}
+
+/// Small xorshift generator : playouts need speed, not quality.
+struct Rng(u64);
+
+impl Rng {
+    /// New generator with a random seed, different for each given index.
+    fn new(index: usize) -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_usize(index);
+        Rng(hasher.finish() | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Random number in 0..bound.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    /// Return true with given probability.
+    fn chance(&mut self, probability: f64) -> bool {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64 <= probability
+    }
+}
+
+/// Is the game over ? We also stop when nobody can move.
+fn finished(state: &Configuration) -> bool {
+    state.game_over()
+        || (state.movements().next().is_none() && state.skip_play().movements().next().is_none())
+}
+
+/// Result of a finished game for the player to move (1 for a win, 0.5 for a draw).
+fn reward(state: &Configuration) -> f64 {
+    match state.value() {
+        v if v < 0 => 1.0,
+        0 => 0.5,
+        _ => 0.0,
+    }
+}
+
+/// Finish the game from given configuration and return the result for the player to move.
+fn playout(state: &Configuration, kind: Playout, rng: &mut Rng) -> f64 {
+    let player = state.current_player;
+    let mut state = *state;
+    let mut movements = Vec::new();
+    let mut passes = 0;
+    for _ in 0..MAX_PLAYOUT_PLIES {
+        if state.game_over() || passes == 2 {
+            break;
+        }
+        movements.clear();
+        movements.extend(state.movements());
+        if movements.is_empty() {
+            passes += 1;
+            state = state.skip_play();
+            continue;
+        }
+        passes = 0;
+        let movement = if kind == Playout::Greedy && rng.chance(GREEDY_PROBABILITY) {
+            *movements
+                .iter()
+                .max_by_key(|movement| state.play(movement).value())
+                .unwrap()
+        } else {
+            movements[rng.below(movements.len())]
+        };
+        state.apply_movement(&movement);
+    }
+    if state.current_player == player {
+        reward(&state)
+    } else {
+        1.0 - reward(&state)
+    }
+}
+
+/// Parameters shared by all iterations.
+struct Settings {
+    playout: Playout,
+    exploration: f64,
+}
+
+/// Node of the search tree.
+struct Node {
+    /// Movement leading here from the parent (None for passes and for the root).
+    movement: Option<Movement>,
+    /// Hash of the configuration, to find it back when reusing the tree.
+    zobrist: u64,
+    visits: u32,
+    /// Sum of the results for the player who moved into this node.
+    wins: f64,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(movement: Option<Movement>, state: &Configuration) -> Self {
+        Node {
+            movement,
+            zobrist: state.zobrist(),
+            visits: 0,
+            wins: 0.0,
+            children: Vec::new(),
+        }
+    }
+
+    /// Take the subtree of given configuration if it is this node or one of its children.
+    fn reuse(self, state: &Configuration) -> Option<Node> {
+        if self.zobrist == state.zobrist() {
+            return Some(self);
+        }
+        self.children
+            .into_iter()
+            .find(|child| child.zobrist == state.zobrist())
+    }
+
+    /// Take the subtree reached by playing given movement.
+    fn into_child(self, movement: Option<Movement>) -> Option<Node> {
+        self.children
+            .into_iter()
+            .find(|child| child.movement == movement)
+    }
+
+    /// Create all children (a single pass one if no move is possible).
+    fn expand(&mut self, state: &Configuration) {
+        self.children = state
+            .movements()
+            .map(|movement| Node::new(Some(movement), &state.play(&movement)))
+            .collect();
+        if self.children.is_empty() {
+            self.children.push(Node::new(None, &state.skip_play()));
+        }
+    }
+
+    /// Child with the best upper confidence bound. Unvisited children come first.
+    fn select(&mut self, exploration: f64) -> &mut Node {
+        let log_visits = f64::from(self.visits).ln();
+        let uct = |child: &Node| {
+            if child.visits == 0 {
+                f64::INFINITY
+            } else {
+                let visits = f64::from(child.visits);
+                child.wins / visits + exploration * (log_visits / visits).sqrt()
+            }
+        };
+        self.children
+            .iter_mut()
+            .max_by(|a, b| uct(a).partial_cmp(&uct(b)).unwrap())
+            .unwrap()
+    }
+
+    /// Run one iteration below this node and return the result for the player to move.
+    fn iterate(&mut self, state: &Configuration, settings: &Settings, rng: &mut Rng) -> f64 {
+        let result = if finished(state) {
+            reward(state)
+        } else if self.visits == 0 && self.children.is_empty() {
+            playout(state, settings.playout, rng)
+        } else {
+            if self.children.is_empty() {
+                self.expand(state);
+            }
+            let child = self.select(settings.exploration);
+            let next_state = match child.movement {
+                Some(movement) => state.play(&movement),
+                None => state.skip_play(),
+            };
+            1.0 - child.iterate(&next_state, settings, rng)
+        };
+        self.visits += 1;
+        self.wins += 1.0 - result;
+        result
+    }
+
+    /// Most visited child.
+    fn best_child(&self) -> Option<&Node> {
+        self.children.iter().max_by_key(|child| child.visits)
+    }
+}
+
+/// Monte Carlo Tree Search strategy.
+/// Trees are kept from one move to the next and searched again from the new configuration.
+/// Several independent trees can be searched in parallel, votes being summed at the root.
+pub struct Mcts {
+    budget: Budget,
+    playout: Playout,
+    exploration: f64,
+    /// One tree per parallel search.
+    trees: Vec<Node>,
+    threads: usize,
+    info: Option<SearchInfo>,
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Mcts::new()
+    }
+}
+
+impl Mcts {
+    /// New strategy with random playouts, a single tree and 10000 iterations per move.
+    pub fn new() -> Self {
+        Mcts {
+            budget: Budget::Iterations(DEFAULT_ITERATIONS),
+            playout: Playout::Random,
+            exploration: DEFAULT_EXPLORATION,
+            trees: Vec::new(),
+            threads: 1,
+            info: None,
+        }
+    }
+
+    /// Run given number of iterations per move.
+    pub fn iterations(self, iterations: u32) -> Self {
+        Mcts {
+            budget: Budget::Iterations(iterations),
+            ..self
+        }
+    }
+
+    /// Search during given number of milliseconds per move instead of a fixed number of
+    /// iterations.
+    pub fn duration(self, duration: u64) -> Self {
+        Mcts {
+            budget: Budget::Time(Duration::from_millis(duration)),
+            ..self
+        }
+    }
+
+    /// Finish games with given kind of playouts.
+    pub fn playout(self, playout: Playout) -> Self {
+        Mcts { playout, ..self }
+    }
+
+    /// Set the exploration constant of the UCT formula.
+    pub fn exploration(self, exploration: f64) -> Self {
+        Mcts {
+            exploration,
+            ..self
+        }
+    }
+
+    /// Search given number of independent trees in parallel (root parallelism).
+    pub fn threads(self, threads: usize) -> Self {
+        Mcts {
+            threads: threads.max(1),
+            trees: Vec::new(),
+            ..self
+        }
+    }
+
+    /// Search all trees from given configuration and return the number of iterations.
+    fn search(&mut self, state: &Configuration) -> u32 {
+        let settings = Settings {
+            playout: self.playout,
+            exploration: self.exploration,
+        };
+        let budget = self.budget;
+        let threads = self.threads as u32;
+        let mut trees = std::mem::take(&mut self.trees);
+        trees.resize_with(self.threads, || Node::new(None, state));
+        let (trees, iterations): (Vec<Node>, Vec<u32>) = trees
+            .into_par_iter()
+            .enumerate()
+            .map(|(index, tree)| {
+                let mut tree = tree.reuse(state).unwrap_or_else(|| Node::new(None, state));
+                tree.movement = None;
+                // expanded up front so that every tree votes, whatever the budget
+                if tree.children.is_empty() && !finished(state) {
+                    tree.expand(state);
+                }
+                let mut rng = Rng::new(index);
+                let mut done = 0;
+                match budget {
+                    Budget::Iterations(iterations) => {
+                        let share =
+                            iterations / threads + (index < (iterations % threads) as usize) as u32;
+                        while done < share {
+                            tree.iterate(state, &settings, &mut rng);
+                            done += 1;
+                        }
+                    }
+                    Budget::Time(duration) => {
+                        let deadline = Instant::now() + duration;
+                        while Instant::now() < deadline {
+                            tree.iterate(state, &settings, &mut rng);
+                            done += 1;
+                        }
+                    }
+                }
+                (tree, done)
+            })
+            .unzip();
+        self.trees = trees;
+        iterations.iter().sum()
+    }
+
+    /// Movement with the most visits summed over all trees, and its win rate.
+    fn vote(&self) -> Option<(Option<Movement>, f64)> {
+        let mut votes: Vec<(Option<Movement>, u32, f64)> = Vec::new();
+        for child in self.trees.iter().flat_map(|tree| tree.children.iter()) {
+            match votes.iter_mut().find(|(m, _, _)| *m == child.movement) {
+                Some(vote) => {
+                    vote.1 += child.visits;
+                    vote.2 += child.wins;
+                }
+                None => votes.push((child.movement, child.visits, child.wins)),
+            }
+        }
+        votes
+            .into_iter()
+            .max_by_key(|&(_, visits, _)| visits)
+            .map(|(movement, visits, wins)| (movement, wins / f64::from(visits.max(1))))
+    }
+}
+
+impl Strategy for Mcts {
+    fn compute_next_move(&mut self, state: &Configuration) -> Option<Movement> {
+        let start = Instant::now();
+        let nodes = self.search(state);
+        let (movement, win_rate) = self.vote()?;
+        // principal variation : most visited children of the first tree
+        let mut pv: Vec<Movement> = movement.into_iter().collect();
+        let mut node = self.trees[0]
+            .children
+            .iter()
+            .find(|child| child.movement == movement);
+        while let Some(child) = node.and_then(Node::best_child) {
+            match child.movement {
+                Some(movement) if child.visits > 0 => pv.push(movement),
+                _ => break,
+            }
+            node = Some(child);
+        }
+        self.info = Some(SearchInfo {
+            depth: pv.len() as u8,
+            score: Score::heuristic(((win_rate * 2.0 - 1.0) * 100.0).round() as i8),
+            nodes: u64::from(nodes),
+            cutoffs: 0,
+            elapsed: start.elapsed(),
+            pv,
+            exact: false,
+        });
+        // keep the subtrees after our move for next time
+        self.trees = std::mem::take(&mut self.trees)
+            .into_iter()
+            .filter_map(|tree| tree.into_child(movement))
+            .collect();
+        movement
+    }
+
+    fn search_info(&self) -> Option<&SearchInfo> {
+        self.info.as_ref()
+    }
+}
+
+impl fmt::Display for Mcts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MCTS (")?;
+        match self.budget {
+            Budget::Iterations(iterations) => write!(f, "{} iterations", iterations)?,
+            Budget::Time(duration) => write!(f, "{}ms", duration.as_millis())?,
+        }
+        write!(
+            f,
+            ", {:?} playouts, {} tree{})",
+            self.playout,
+            self.threads,
+            if self.threads > 1 { "s" } else { "" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    #[test]
+    fn tiny_budgets_still_move() {
+        let board = Board::default();
+        let state = Configuration::new(&board);
+        let strategies = vec![
+            Mcts::new().iterations(1),
+            Mcts::new().iterations(0),
+            Mcts::new().iterations(3).threads(4),
+            Mcts::new().duration(0).threads(2),
+        ];
+        for mut strategy in strategies {
+            let movement = strategy.compute_next_move(&state);
+            assert!(
+                movement.is_some_and(|movement| state.check_move(&movement)),
+                "{} played {:?}",
+                strategy,
+                movement
+            );
+        }
+    }
+}
@@ -1,75 +1,152 @@
 //! Implementation of the min max algorithm.
+use super::anytime::{publish_iterative_deepening, DepthSearch, Stop};
+use super::evaluation::{Evaluator, Material};
+use super::score::Score;
+use super::search::{Search, SearchInfo};
 use super::Strategy;
 use crate::configuration::{Configuration, Movement};
-use crate::shmem::AtomicMove;
-use std::fmt;
 use rayon::prelude::*;
+use std::fmt;
 
 /// Min-Max algorithm with a given recursion depth.
-pub struct MinMax(pub u8);
+/// Leaves are evaluated by given `Evaluator` (material count by default).
+pub struct MinMax<E: Evaluator = Material> {
+    depth: u8,
+    evaluator: E,
+    info: Option<SearchInfo>,
+}
+
+impl MinMax {
+    /// New min-max strategy with given recursion depth.
+    pub fn new(depth: u8) -> Self {
+        MinMax {
+            depth,
+            evaluator: Material(),
+            info: None,
+        }
+    }
+}
+
+impl<E: Evaluator> MinMax<E> {
+    /// Evaluate leaves with given `Evaluator`.
+    pub fn with_evaluator<F: Evaluator>(self, evaluator: F) -> MinMax<F> {
+        MinMax {
+            depth: self.depth,
+            evaluator,
+            info: None,
+        }
+    }
+}
 
-impl Strategy for MinMax {
+impl<E: Evaluator> Strategy for MinMax<E> {
     fn compute_next_move(&mut self, state: &Configuration) -> Option<Movement> {
-        state.movements()
+        self.info = self.search_depth(state, self.depth, &Stop::default());
+        self.info.as_ref().and_then(SearchInfo::movement)
+    }
+
+    fn search_info(&self) -> Option<&SearchInfo> {
+        self.info.as_ref()
+    }
+}
+
+impl<E: Evaluator> DepthSearch for MinMax<E> {
+    fn search_depth(
+        &mut self,
+        state: &Configuration,
+        depth: u8,
+        stop: &Stop,
+    ) -> Option<SearchInfo> {
+        let search = Search::new(None, &self.evaluator, stop, depth);
+        search.node();
+        let (movement, score) = if state.game_over() {
+            (None, search.final_score(state, depth))
+        } else {
+            state
+                .movements()
                 .collect::<Vec<Movement>>()
                 .par_iter()
-                .map(|coup| (coup, -negamax(self.0 - 1, state.play(&coup))))
+                .map(|coup| (coup, -negamax(depth - 1, state.play(coup), &search)))
                 .max_by_key(|&(_, val)| val)
-                .map(|(res, _)| *res)
+                .map(|(res, val)| (Some(*res), val))
+                // blocked : we pass
+                .unwrap_or_else(|| (None, -negamax(depth - 1, state.skip_play(), &search)))
+        };
+        if stop.is_raised() {
+            return None;
+        }
+        let pv = principal_variation(state, movement, depth, &self.evaluator, stop);
+        Some(search.info(depth, score, pv))
     }
 }
 
-impl fmt::Display for MinMax {
+impl<E: Evaluator> fmt::Display for MinMax<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Min - Max (max level: {})", self.0)
+        write!(
+            f,
+            "Min - Max (max level: {}, {} evaluation)",
+            self.depth, self.evaluator
+        )
     }
 }
 
-fn negamax(profondeur: u8, state: Configuration) -> i8 {
-    if profondeur == 0 {
-        -state.value()
+fn negamax<E: Evaluator>(profondeur: u8, state: Configuration, search: &Search<E>) -> Score {
+    search.node();
+    if state.game_over() {
+        search.final_score(&state, profondeur)
+    } else if profondeur == 0 {
+        search.evaluate(&state)
+    } else if search.stop.is_raised() {
+        Score::DRAW
     } else {
-        state.movements()
-             .into_iter()
-             .map(|coup| -negamax(profondeur - 1, state.play(&coup)))
-             .max()
-             .unwrap_or(-state.value())
+        state
+            .movements()
+            .map(|coup| -negamax(profondeur - 1, state.play(&coup), search))
+            .max()
+            // blocked : we pass
+            .unwrap_or_else(|| -negamax(profondeur - 1, state.skip_play(), search))
     }
 }
 
-fn minimax(profondeur: u8, maximizing: bool, state: Configuration) -> i8 {
-    if profondeur == 0 {
-        if maximizing {
-            -state.value()
-        } else {
-            state.value()
+/// Replay the search below each move of the principal variation to find the next one.
+/// This costs a fraction of the main search since we only look below the chosen moves.
+/// The variation is truncated if we are asked to stop meanwhile.
+fn principal_variation<E: Evaluator>(
+    state: &Configuration,
+    movement: Option<Movement>,
+    depth: u8,
+    evaluator: &E,
+    stop: &Stop,
+) -> Vec<Movement> {
+    // separate search : we do not want to count these nodes
+    let search = Search::new(None, evaluator, stop, depth);
+    let mut pv = Vec::new();
+    let mut state = *state;
+    let mut next_move = movement;
+    let mut remaining = depth;
+    while let Some(movement) = next_move {
+        pv.push(movement);
+        state.apply_movement(&movement);
+        remaining -= 1;
+        if remaining == 0 {
+            break;
+        }
+        next_move = state
+            .movements()
+            .map(|coup| (coup, -negamax(remaining - 1, state.play(&coup), &search)))
+            .max_by_key(|&(_, val)| val)
+            .map(|(coup, _)| coup);
+        if stop.is_raised() {
+            break;
         }
-    } else { // Séquentiel sur la fin
-        state.movements()
-            .into_iter()
-            .map(|coup| minimax(profondeur - 1, ! maximizing, state.play(&coup)))
-            .max_by_key(|&val| {
-                if maximizing {
-                    val
-                } else {
-                    -val
-                }
-            })
-            .unwrap_or(if maximizing {
-                            -state.value() 
-                        } else { 
-                            state.value()
-            })
     }
+    pv
 }
 
 /// Anytime min max algorithm.
 /// Any time algorithms will compute until a deadline is hit and the process is killed.
 /// They are therefore run in another process and communicate through shared memory.
-/// This function is intended to be called from blobwar_iterative_deepening.
-pub fn min_max_anytime(state: &Configuration) {
-    let mut movement = AtomicMove::connect().expect("failed connecting to shmem");
-    for depth in 1..100 {
-        movement.store(MinMax(depth).compute_next_move(state));
-    }
+/// This function is intended to be called from blobwar_iterative_deepening, reports are stored
+/// in the shared memory segment with given name.
+pub fn min_max_anytime(state: &Configuration, shmem: &str) {
+    publish_iterative_deepening(&mut MinMax::new(1), state, shmem)
 }
@@ -7,6 +7,31 @@ pub trait Strategy: fmt::Display {
     /// Take current `Configuration` and return what to do next.
     /// None if no move is possible.
     fn compute_next_move(&mut self, configuration: &Configuration) -> Option<Movement>;
+
+    /// Report of the search behind the last computed move, for strategies which search.
+    fn search_info(&self) -> Option<&SearchInfo> {
+        None
+    }
+
+    /// Did the last call to `compute_next_move` ask to take back our previous turn instead
+    /// of playing ?
+    fn wants_undo(&self) -> bool {
+        false
+    }
+}
+
+impl<S: Strategy + ?Sized> Strategy for Box<S> {
+    fn compute_next_move(&mut self, configuration: &Configuration) -> Option<Movement> {
+        (**self).compute_next_move(configuration)
+    }
+
+    fn search_info(&self) -> Option<&SearchInfo> {
+        (**self).search_info()
+    }
+
+    fn wants_undo(&self) -> bool {
+        (**self).wants_undo()
+    }
 }
 
 pub mod human;
@@ -18,9 +43,26 @@ pub use self::greedy::Greedy;
 pub mod minmax;
 pub use self::minmax::{min_max_anytime, MinMax};
 pub mod evil;
-pub use self::evil::{Evil};
+pub use self::evil::Evil;
+pub mod anytime;
+pub use self::anytime::{iterative_deepening, DepthSearch, Stop};
+pub mod evaluation;
+pub use self::evaluation::{Evaluator, Frontier, Material, Mobility, Safety};
+pub mod endgame;
+pub use self::endgame::Endgame;
+pub mod score;
+pub use self::score::{ParseScoreError, Score};
+pub mod search;
+pub use self::search::SearchInfo;
+pub mod transposition;
+pub use self::transposition::TranspositionTable;
 pub mod alphabeta;
 pub use self::alphabeta::{alpha_beta_anytime, AlphaBeta};
+pub mod mcts;
+pub use self::mcts::{Mcts, Playout};
 pub mod iterative;
 pub use self::iterative::IterativeDeepening;
 pub use self::iterative::IterativeStrategy;
+pub use self::iterative::{serve, HelperNotFound};
+pub mod registry;
+pub use self::registry::{parse_strategy, SpecError};
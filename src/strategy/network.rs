@@ -1,16 +1,19 @@
 //! Network player (server side)
+//! Clients receive serialized configurations, one per line, and answer one movement per line,
+//! either in JSON (`{"Jump":[3,17]}`, `null` to pass) or in Ataxx notation (`a1c3`, `--` or
+//! `0000` to pass).
 use super::Strategy;
 use crate::configuration::{Configuration, Movement};
 
-use serde_json::{de, Deserializer, StreamDeserializer};
 use std::fmt;
 use std::io::prelude::*;
+use std::io::{BufReader, Lines};
 use std::net::TcpStream;
 
 /// Let a remote client enter moves.
 pub struct NetworkPlayer {
     connection: TcpStream,
-    movements: StreamDeserializer<'static, de::IoRead<TcpStream>, Option<Movement>>,
+    movements: Lines<BufReader<TcpStream>>,
     name: String,
 }
 
@@ -25,7 +28,7 @@ impl NetworkPlayer {
     pub fn new(data: TcpStream) -> Self {
         let origin = data.peer_addr().unwrap().to_string();
         let connection = data.try_clone().unwrap();
-        let movements = Deserializer::from_reader(data).into_iter::<Option<Movement>>();
+        let movements = BufReader::new(data).lines();
         NetworkPlayer {
             connection,
             movements,
@@ -41,6 +44,29 @@ impl Strategy for NetworkPlayer {
         self.connection
             .write_all(message.into_bytes().as_slice())
             .expect("sending configuration remotely failed");
-        self.movements.next().unwrap().unwrap()
+        // errors are reported and count as passes
+        match self.movements.next() {
+            Some(Ok(line)) => parse_answer(line.trim()).unwrap_or_else(|| {
+                eprintln!("{}: invalid movement received '{}'", self.name, line.trim());
+                None
+            }),
+            Some(Err(e)) => {
+                eprintln!("{}: failed reading movement: {}", self.name, e);
+                None
+            }
+            None => {
+                eprintln!("{}: connection closed", self.name);
+                None
+            }
+        }
+    }
+}
+
+/// Read the movement (None for a pass) answered by a client.
+fn parse_answer(line: &str) -> Option<Option<Movement>> {
+    match line {
+        "--" | "0000" => Some(None),
+        _ if line.starts_with('{') || line == "null" => serde_json::from_str(line).ok(),
+        _ => line.parse().ok().map(Some),
     }
 }
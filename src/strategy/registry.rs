@@ -0,0 +1,423 @@
+//! Build strategies out of textual specifications.
+//! A specification is a strategy name followed by colon separated parameters, given either
+//! in order or by name : `alphabeta:6`, `alphabeta:depth=6:eval=mobility` or
+//! `iterative:alphabeta:ms=500`. Missing parameters take default values.
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{
+    AlphaBeta, Evil, Frontier, Greedy, Human, IterativeDeepening, IterativeStrategy, Material,
+    Mcts, MinMax, Mobility, Playout, Safety, Strategy,
+};
+
+/// All available strategies with their parameters (in order) and default values.
+pub const STRATEGIES: &[(&str, &[(&str, &str)])] = &[
+    ("greedy", &[]),
+    ("human", &[]),
+    ("minmax", &[("depth", "4"), ("eval", "material")]),
+    (
+        "alphabeta",
+        &[("depth", "6"), ("eval", "material"), ("endgame", "0")],
+    ),
+    ("evil", &[("depth", "6"), ("eval", "material")]),
+    (
+        "mcts",
+        &[
+            ("iterations", "10000"),
+            ("ms", "none"),
+            ("playout", "random"),
+            ("threads", "1"),
+            ("c", "1.4"),
+        ],
+    ),
+    (
+        "iterative",
+        &[
+            ("algorithm", "alphabeta"),
+            ("ms", "2000"),
+            ("subprocess", "false"),
+            ("worker", "false"),
+        ],
+    ),
+];
+
+/// Available evaluations, for the `eval` parameter.
+pub const EVALUATIONS: &[&str] = &["material", "mobility", "frontier", "safety"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Everything which can go wrong when reading a specification.
+pub enum SpecError {
+    /// No such strategy.
+    UnknownStrategy(String),
+    /// The strategy has no parameter of this name.
+    UnknownParameter {
+        /// Strategy name.
+        strategy: String,
+        /// Given parameter name.
+        parameter: String,
+    },
+    /// More parameters than the strategy accepts were given in order.
+    TooManyParameters(String),
+    /// A parameter was given twice.
+    DuplicateParameter(String),
+    /// A value could not be parsed.
+    InvalidValue {
+        /// Parameter name.
+        parameter: String,
+        /// Given value.
+        value: String,
+    },
+}
+
+/// Usage of a strategy, like `minmax[:depth=4][:eval=material]`.
+fn usage(name: &str, parameters: &[(&str, &str)]) -> String {
+    parameters
+        .iter()
+        .fold(name.to_owned(), |usage, (parameter, default)| {
+            format!("{}[:{}={}]", usage, parameter, default)
+        })
+}
+
+/// Usage of given strategy (its name if it is unknown).
+fn strategy_usage(strategy: &str) -> String {
+    STRATEGIES
+        .iter()
+        .find(|(name, _)| *name == strategy)
+        .map_or_else(
+            || strategy.to_owned(),
+            |(name, parameters)| usage(name, parameters),
+        )
+}
+
+/// Describe all available strategies, one per line.
+pub fn available_strategies() -> String {
+    STRATEGIES
+        .iter()
+        .map(|(name, parameters)| usage(name, parameters))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpecError::UnknownStrategy(name) => write!(
+                f,
+                "unknown strategy '{}', available strategies are:\n{}",
+                name,
+                available_strategies()
+            ),
+            SpecError::UnknownParameter {
+                strategy,
+                parameter,
+            } => write!(
+                f,
+                "unknown parameter '{}' for {}, usage is {}",
+                parameter,
+                strategy,
+                strategy_usage(strategy)
+            ),
+            SpecError::TooManyParameters(strategy) => write!(
+                f,
+                "too many parameters for {}, usage is {}",
+                strategy,
+                strategy_usage(strategy)
+            ),
+            SpecError::DuplicateParameter(parameter) => {
+                write!(f, "parameter '{}' is given twice", parameter)
+            }
+            SpecError::InvalidValue { parameter, value } if parameter == "eval" => write!(
+                f,
+                "invalid evaluation '{}', available evaluations are {}",
+                value,
+                EVALUATIONS.join(", ")
+            ),
+            SpecError::InvalidValue { parameter, value } => {
+                write!(f, "invalid value '{}' for parameter {}", value, parameter)
+            }
+        }
+    }
+}
+
+impl Error for SpecError {}
+
+/// Parameters of a specification, sorted as in `STRATEGIES`.
+struct Parameters<'s> {
+    names: &'static [(&'static str, &'static str)],
+    values: Vec<Option<&'s str>>,
+}
+
+impl<'s> Parameters<'s> {
+    fn parse(
+        strategy: &str,
+        names: &'static [(&'static str, &'static str)],
+        arguments: impl Iterator<Item = &'s str>,
+        defaults: &[(&str, &'s str)],
+    ) -> Result<Self, SpecError> {
+        let mut values = vec![None; names.len()];
+        for (index, argument) in arguments.enumerate() {
+            let (position, value) = match argument.find('=') {
+                Some(equal) => {
+                    let parameter = &argument[..equal];
+                    let position = names
+                        .iter()
+                        .position(|(name, _)| *name == parameter)
+                        .ok_or_else(|| SpecError::UnknownParameter {
+                            strategy: strategy.to_owned(),
+                            parameter: parameter.to_owned(),
+                        })?;
+                    (position, &argument[equal + 1..])
+                }
+                None if index < names.len() => (index, argument),
+                None => return Err(SpecError::TooManyParameters(strategy.to_owned())),
+            };
+            if values[position].replace(value).is_some() {
+                return Err(SpecError::DuplicateParameter(names[position].0.to_owned()));
+            }
+        }
+        for (parameter, default) in defaults {
+            if let Some(position) = names.iter().position(|(name, _)| name == parameter) {
+                values[position].get_or_insert(default);
+            }
+        }
+        Ok(Parameters { names, values })
+    }
+
+    /// Given or default value of given parameter, "none" if there is no default.
+    fn raw(&self, parameter: &str) -> &'s str {
+        let position = self
+            .names
+            .iter()
+            .position(|(name, _)| *name == parameter)
+            .expect("undeclared parameter");
+        self.values[position].unwrap_or(self.names[position].1)
+    }
+
+    /// Parse given parameter, None if it is not set.
+    fn get<T: FromStr>(&self, parameter: &str) -> Result<Option<T>, SpecError> {
+        match self.raw(parameter) {
+            "none" => Ok(None),
+            value => value
+                .parse()
+                .map(Some)
+                .map_err(|_| SpecError::InvalidValue {
+                    parameter: parameter.to_owned(),
+                    value: value.to_owned(),
+                }),
+        }
+    }
+
+    /// Parse given parameter which has a default value.
+    fn value<T: FromStr>(&self, parameter: &str) -> Result<T, SpecError> {
+        self.get(parameter)?.ok_or_else(|| SpecError::InvalidValue {
+            parameter: parameter.to_owned(),
+            value: "none".to_owned(),
+        })
+    }
+
+    /// Parse given parameter which must be at least one.
+    fn positive<T: FromStr + PartialOrd + From<u8>>(
+        &self,
+        parameter: &str,
+    ) -> Result<T, SpecError> {
+        let value = self.value(parameter)?;
+        if value >= T::from(1) {
+            Ok(value)
+        } else {
+            Err(SpecError::InvalidValue {
+                parameter: parameter.to_owned(),
+                value: self.raw(parameter).to_owned(),
+            })
+        }
+    }
+
+    /// Check given parameter is amongst given choices.
+    fn choice(&self, parameter: &str, choices: &[&str]) -> Result<&'s str, SpecError> {
+        let value = self.raw(parameter);
+        if choices.contains(&value) {
+            Ok(value)
+        } else {
+            Err(SpecError::InvalidValue {
+                parameter: parameter.to_owned(),
+                value: value.to_owned(),
+            })
+        }
+    }
+}
+
+/// Box given strategy once its leaves evaluator is set from the `eval` parameter.
+macro_rules! evaluated {
+    ($parameters: expr, $strategy: expr) => {
+        match $parameters.choice("eval", EVALUATIONS)? {
+            "mobility" => Box::new($strategy.with_evaluator(Mobility())) as Box<dyn Strategy>,
+            "frontier" => Box::new($strategy.with_evaluator(Frontier())),
+            "safety" => Box::new($strategy.with_evaluator(Safety())),
+            _ => Box::new($strategy.with_evaluator(Material())),
+        }
+    };
+}
+
+/// Build the strategy described by given specification.
+pub fn parse_strategy(spec: &str) -> Result<Box<dyn Strategy>, SpecError> {
+    parse_strategy_with_defaults(spec, &[])
+}
+
+/// Build the strategy described by given specification, replacing the default values of
+/// given parameters. Parameters the strategy does not have are ignored.
+pub fn parse_strategy_with_defaults(
+    spec: &str,
+    defaults: &[(&str, &str)],
+) -> Result<Box<dyn Strategy>, SpecError> {
+    let mut arguments = spec.split(':');
+    let name = arguments.next().unwrap_or_default();
+    let names = STRATEGIES
+        .iter()
+        .find(|(strategy, _)| *strategy == name)
+        .map(|(_, names)| *names)
+        .ok_or_else(|| SpecError::UnknownStrategy(name.to_owned()))?;
+    let parameters = Parameters::parse(name, names, arguments, defaults)?;
+    Ok(match name {
+        "greedy" => Box::new(Greedy()),
+        "human" => Box::new(Human::new()),
+        "minmax" => evaluated!(parameters, MinMax::new(parameters.positive("depth")?)),
+        "alphabeta" => evaluated!(
+            parameters,
+            AlphaBeta::new(parameters.positive("depth")?).endgame(parameters.value("endgame")?)
+        ),
+        "evil" => evaluated!(parameters, Evil::new(parameters.positive("depth")?)),
+        "mcts" => {
+            let mcts = Mcts::new()
+                .iterations(parameters.positive("iterations")?)
+                .playout(match parameters.choice("playout", &["random", "greedy"])? {
+                    "greedy" => Playout::Greedy,
+                    _ => Playout::Random,
+                })
+                .threads(parameters.positive("threads")?)
+                .exploration(parameters.value("c")?);
+            match parameters.get("ms")? {
+                Some(duration) => Box::new(mcts.duration(duration)),
+                None => Box::new(mcts),
+            }
+        }
+        "iterative" => {
+            let algorithm =
+                match parameters.choice("algorithm", &["minmax", "alphabeta", "evil"])? {
+                    "minmax" => IterativeStrategy::MinMax,
+                    "evil" => IterativeStrategy::Evil,
+                    _ => IterativeStrategy::AlphaBeta,
+                };
+            let iterative = IterativeDeepening::new(algorithm).duration(parameters.value("ms")?);
+            if parameters.value("worker")? {
+                Box::new(iterative.in_worker())
+            } else if parameters.value("subprocess")? {
+                Box::new(iterative.in_subprocess())
+            } else {
+                Box::new(iterative)
+            }
+        }
+        _ => unreachable!("strategy missing from the registry"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(spec: &str) -> Option<SpecError> {
+        parse_strategy(spec).err()
+    }
+
+    fn invalid(parameter: &str, value: &str) -> Option<SpecError> {
+        Some(SpecError::InvalidValue {
+            parameter: parameter.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+
+    #[test]
+    fn positional_and_named_parameters() {
+        let names = STRATEGIES[3].1;
+        let positional =
+            Parameters::parse("alphabeta", names, "7:mobility".split(':'), &[]).unwrap();
+        let named =
+            Parameters::parse("alphabeta", names, "eval=mobility:depth=7".split(':'), &[]).unwrap();
+        let mixed = Parameters::parse("alphabeta", names, "7:endgame=9".split(':'), &[]).unwrap();
+        for parameters in &[&positional, &named] {
+            assert_eq!(parameters.value::<u8>("depth"), Ok(7));
+            assert_eq!(parameters.raw("eval"), "mobility");
+            assert_eq!(parameters.value::<usize>("endgame"), Ok(0));
+        }
+        assert_eq!(mixed.value::<u8>("depth"), Ok(7));
+        assert_eq!(mixed.value::<usize>("endgame"), Ok(9));
+        assert_eq!(
+            parse_strategy("alphabeta:7").unwrap().to_string(),
+            parse_strategy("alphabeta:depth=7").unwrap().to_string()
+        );
+        for &(name, _) in STRATEGIES {
+            assert!(parse_strategy(name).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn replaced_defaults() {
+        let timed = |spec| {
+            parse_strategy_with_defaults(spec, &[("ms", "100")])
+                .unwrap()
+                .to_string()
+        };
+        assert!(timed("iterative").contains(" 100ms"));
+        assert!(timed("iterative:alphabeta:500").contains(" 500ms"));
+        assert!(timed("iterative:ms=300").contains(" 300ms"));
+        assert!(timed("mcts").contains("100ms"));
+        assert_eq!(
+            timed("alphabeta:3"),
+            parse_strategy("alphabeta:3").unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn duplicate_and_unknown_parameters() {
+        assert_eq!(
+            error("alphabeta:5:depth=6"),
+            Some(SpecError::DuplicateParameter("depth".to_owned()))
+        );
+        assert_eq!(
+            error("mcts:ms=5:ms=6"),
+            Some(SpecError::DuplicateParameter("ms".to_owned()))
+        );
+        assert_eq!(
+            error("minmax:width=3"),
+            Some(SpecError::UnknownParameter {
+                strategy: "minmax".to_owned(),
+                parameter: "width".to_owned(),
+            })
+        );
+        assert_eq!(
+            error("greedy:3"),
+            Some(SpecError::TooManyParameters("greedy".to_owned()))
+        );
+        assert_eq!(
+            error("alphabta:3"),
+            Some(SpecError::UnknownStrategy("alphabta".to_owned()))
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(error("minmax:0"), invalid("depth", "0"));
+        assert_eq!(error("alphabeta:depth=0"), invalid("depth", "0"));
+        assert_eq!(error("evil:0"), invalid("depth", "0"));
+        assert_eq!(error("evil:-1"), invalid("depth", "-1"));
+        assert_eq!(error("minmax:300"), invalid("depth", "300"));
+        assert_eq!(error("mcts:iterations=0"), invalid("iterations", "0"));
+        assert_eq!(error("mcts:threads=0"), invalid("threads", "0"));
+        assert_eq!(error("mcts:playout=smart"), invalid("playout", "smart"));
+        assert_eq!(error("alphabeta:4:chess"), invalid("eval", "chess"));
+        assert_eq!(error("iterative:negamax"), invalid("algorithm", "negamax"));
+        assert_eq!(error("iterative:ms=soon"), invalid("ms", "soon"));
+        assert_eq!(error("iterative:worker=yes"), invalid("worker", "yes"));
+        assert!(parse_strategy("minmax:1").is_ok());
+        assert!(parse_strategy("mcts:1").is_ok());
+    }
+}
@@ -0,0 +1,271 @@
+//! Values of positions, as computed by searches.
+//! A score is either a heuristic evaluation or the result of a finished game. Finished games
+//! are encoded as "win (or loss) in N plies with given final margin" and are above (below)
+//! every evaluation. Faster wins score more, and so do slower losses.
+//! Scores are serialized in their displayed form.
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+use std::str::FromStr;
+
+/// Score of a win at the root, with no margin.
+const WIN: i16 = 30_000;
+/// Score lost per ply before the end of the game. Margins are always smaller.
+const PLY: i16 = 128;
+/// Scores beyond this (in absolute value) are finished games.
+const DECIDED: i16 = 10_000;
+/// Longest games (in plies from the root) whose results are still above every evaluation.
+pub(crate) const MAX_PLIES: u8 = ((WIN - DECIDED - PLY) / PLY) as u8;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Value of a position for the player to move.
+pub struct Score(i16);
+
+impl Score {
+    /// Above any possible score.
+    pub const INFINITY: Score = Score(WIN + PLY);
+    /// Neutral score.
+    pub const DRAW: Score = Score(0);
+
+    /// Score of an evaluation.
+    pub fn heuristic(value: i8) -> Self {
+        Score(i16::from(value))
+    }
+
+    /// Score of a game which ended given number of plies after the root with given final
+    /// margin (blobs of the player to move minus blobs of his adversary).
+    pub fn finished(margin: i8, plies: u8) -> Self {
+        debug_assert!(plies <= MAX_PLIES, "game too long for the score encoding");
+        let margin = i16::from(margin).clamp(1 - PLY, PLY - 1);
+        let distance = i16::from(plies) * PLY;
+        match margin {
+            0 => Score::DRAW,
+            m if m > 0 => Score(WIN - distance + m),
+            m => Score(-WIN + distance + m),
+        }
+    }
+
+    /// Is this a won or lost game ?
+    pub fn is_decided(self) -> bool {
+        self.0.abs() > DECIDED
+    }
+
+    /// For won or lost games, number of plies from the root to the end of the game.
+    pub fn plies(self) -> Option<u8> {
+        if self.is_decided() {
+            Some(((WIN - self.0.abs() + PLY - 1) / PLY) as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Evaluation, or final margin for won or lost games.
+    pub fn value(self) -> i16 {
+        match self.plies() {
+            Some(plies) => self.0 - self.0.signum() * (WIN - i16::from(plies) * PLY),
+            None => self.0,
+        }
+    }
+
+    /// Convert a score relative to the root into one relative to a node at given ply,
+    /// for storage in a transposition table.
+    pub(crate) fn to_node(self, ply: u8) -> Self {
+        self.shift(ply, 1)
+    }
+
+    /// Convert a score relative to a node at given ply into one relative to the root.
+    pub(crate) fn to_root(self, ply: u8) -> Self {
+        self.shift(ply, -1)
+    }
+
+    fn shift(self, ply: u8, direction: i16) -> Self {
+        if self.is_decided() {
+            Score(self.0 + self.0.signum() * direction * i16::from(ply) * PLY)
+        } else {
+            self
+        }
+    }
+
+    /// Raw bits, for packing.
+    pub(crate) fn bits(self) -> u16 {
+        self.0 as u16
+    }
+
+    /// Score from raw bits.
+    pub(crate) fn from_bits(bits: u16) -> Self {
+        Score(bits as i16)
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score(-self.0)
+    }
+}
+
+impl Add<i16> for Score {
+    type Output = Score;
+    fn add(self, other: i16) -> Score {
+        Score(self.0 + other)
+    }
+}
+
+impl Sub<i16> for Score {
+    type Output = Score;
+    fn sub(self, other: i16) -> Score {
+        Score(self.0 - other)
+    }
+}
+
+impl fmt::Display for Score {
+    /// Evaluations are displayed as is, finished games like chess mates :
+    /// `#+3 (+12)` is a win in 3 plies by 12 blobs, `#-2 (-5)` a loss in 2 plies by 5 blobs.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.plies() {
+            Some(plies) => write!(
+                f,
+                "#{}{} ({:+})",
+                if self.0 > 0 { '+' } else { '-' },
+                plies,
+                self.value()
+            ),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl Serialize for Score {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Score {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Text which is not a displayed `Score`.
+pub struct ParseScoreError(String);
+
+impl fmt::Display for ParseScoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid score '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseScoreError {}
+
+impl FromStr for Score {
+    type Err = ParseScoreError;
+    /// Read back a displayed score.
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let error = || ParseScoreError(string.to_owned());
+        match string.strip_prefix('#') {
+            Some(finished) => {
+                let (plies, margin) = finished.split_once(" (").ok_or_else(error)?;
+                let (won, plies) = match plies.split_at(plies.len().min(1)) {
+                    ("+", plies) => (true, plies),
+                    ("-", plies) => (false, plies),
+                    _ => return Err(error()),
+                };
+                let plies: u8 = plies.parse().map_err(|_| error())?;
+                let margin: i8 = margin
+                    .strip_suffix(')')
+                    .and_then(|margin| margin.parse().ok())
+                    .ok_or_else(error)?;
+                if margin == 0 || won != (margin > 0) || plies > MAX_PLIES {
+                    return Err(error());
+                }
+                Ok(Score::finished(margin, plies))
+            }
+            None => string.parse().map(Score::heuristic).map_err(|_| error()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished_scores() -> impl Iterator<Item = Score> {
+        [-127i8, -64, -1, 1, 12, 64, 127]
+            .iter()
+            .flat_map(|&margin| (0..=MAX_PLIES).map(move |plies| Score::finished(margin, plies)))
+    }
+
+    #[test]
+    fn finished_games_decode() {
+        for margin in [-127i8, -64, -1, 1, 12, 64, 127].iter().cloned() {
+            for plies in 0..=MAX_PLIES {
+                let score = Score::finished(margin, plies);
+                assert!(score.is_decided() && -Score::INFINITY < score && score < Score::INFINITY);
+                assert_eq!(score.plies(), Some(plies));
+                assert_eq!(score.value(), i16::from(margin));
+            }
+        }
+        assert_eq!(Score::finished(0, 7), Score::DRAW);
+        assert!(Score::finished(1, MAX_PLIES) > Score::heuristic(i8::MAX));
+        assert!(Score::finished(-1, MAX_PLIES) < Score::heuristic(i8::MIN));
+        assert!(Score::finished(1, 3) > Score::finished(64, 5));
+        assert!(Score::finished(-64, 3) < Score::finished(-1, 5));
+    }
+
+    #[test]
+    fn table_conversions_round_trip() {
+        for score in finished_scores() {
+            let plies = score.plies().unwrap();
+            for ply in 0..=plies {
+                let stored = score.to_node(ply);
+                assert_eq!(stored.plies(), Some(plies - ply));
+                assert_eq!(stored.to_root(ply), score);
+            }
+        }
+        let evaluation = Score::heuristic(-42);
+        assert_eq!(evaluation.to_node(9), evaluation);
+        assert_eq!(evaluation.to_root(9), evaluation);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        assert_eq!(Score::finished(12, 3).to_string(), "#+3 (+12)");
+        assert_eq!(Score::finished(-5, 2).to_string(), "#-2 (-5)");
+        assert_eq!(Score::heuristic(-7).to_string(), "-7");
+        for score in finished_scores().chain((-128..=127).map(Score::heuristic)) {
+            assert_eq!(score.to_string().parse(), Ok(score));
+        }
+        for invalid in &[
+            "",
+            "#+3",
+            "#+3 (-2)",
+            "#-3 (+2)",
+            "#+3 (0)",
+            "#+300 (+2)",
+            "128",
+            "x",
+        ] {
+            assert!(invalid.parse::<Score>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn json_uses_displayed_form() {
+        let score = Score::finished(12, 3);
+        assert_eq!(serde_json::to_string(&score).unwrap(), r##""#+3 (+12)""##);
+        assert_eq!(
+            serde_json::from_str::<Score>(r##""#+3 (+12)""##).unwrap(),
+            score
+        );
+        assert_eq!(
+            serde_json::to_string(&Score::heuristic(-7)).unwrap(),
+            r#""-7""#
+        );
+        assert!(serde_json::from_str::<Score>("29873").is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! State shared by all the nodes (and all the rayon workers) of one search
+//! and report of what the search did.
+use super::anytime::Stop;
+use super::evaluation::Evaluator;
+use super::score::Score;
+use super::transposition::{Entry, TranspositionTable, MIN_TABLE_DEPTH};
+use crate::configuration::{Configuration, Movement};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+/// What a search found and how much work it took.
+pub struct SearchInfo {
+    /// Depth of the last completed search.
+    pub depth: u8,
+    /// Value of the position for the player to move.
+    pub score: Score,
+    /// Number of visited nodes.
+    pub nodes: u64,
+    /// Number of beta cutoffs.
+    pub cutoffs: u64,
+    /// Time spent searching.
+    pub elapsed: Duration,
+    /// Expected sequence of movements, starting with the chosen one.
+    pub pv: Vec<Movement>,
+    /// Was the game searched to its end (score is then the final margin) ?
+    pub exact: bool,
+}
+
+impl SearchInfo {
+    /// Chosen movement (None if no move is possible).
+    pub fn movement(&self) -> Option<Movement> {
+        self.pv.first().cloned()
+    }
+
+    /// Search speed.
+    pub fn nodes_per_second(&self) -> u64 {
+        let micros = self.elapsed.as_micros().max(1);
+        (u128::from(self.nodes) * 1_000_000 / micros) as u64
+    }
+}
+
+impl fmt::Display for SearchInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "depth {} score {}{} nodes {} cutoffs {} time {}ms ({} nodes/s) pv",
+            self.depth,
+            self.score,
+            if self.exact { " (exact)" } else { "" },
+            self.nodes,
+            self.cutoffs,
+            self.elapsed.as_millis(),
+            self.nodes_per_second()
+        )?;
+        for movement in &self.pv {
+            write!(f, " {}", movement)?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything a recursive search needs besides the position itself.
+pub(crate) struct Search<'s, E: Evaluator> {
+    /// Table of already searched positions (if the algorithm uses one).
+    table: Option<&'s TranspositionTable>,
+    /// Evaluation of the leaves.
+    evaluator: &'s E,
+    /// Raised when the search should be abandoned.
+    pub stop: &'s Stop,
+    /// Depth of the root, to know how far from it a node is.
+    depth: u8,
+    nodes: AtomicU64,
+    cutoffs: AtomicU64,
+    start: Instant,
+}
+
+impl<'s, E: Evaluator> Search<'s, E> {
+    /// Start a new search of given depth.
+    pub fn new(
+        table: Option<&'s TranspositionTable>,
+        evaluator: &'s E,
+        stop: &'s Stop,
+        depth: u8,
+    ) -> Self {
+        Search {
+            table,
+            evaluator,
+            stop,
+            depth,
+            nodes: Default::default(),
+            cutoffs: Default::default(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Number of plies between the root and a node with given remaining depth.
+    fn ply(&self, remaining: u8) -> u8 {
+        self.depth.saturating_sub(remaining)
+    }
+
+    /// Look given zobrist key of a node with given remaining depth up in the table.
+    pub fn probe(&self, key: u64, remaining: u8) -> Option<Entry> {
+        self.table
+            .and_then(|table| table.probe(key))
+            .map(|entry| Entry {
+                score: entry.score.to_root(self.ply(remaining)),
+                ..entry
+            })
+    }
+
+    /// Remember given entry of a node with given remaining depth in the table.
+    pub fn store(&self, key: u64, remaining: u8, entry: Entry) {
+        if let Some(table) = self.table {
+            let score = entry.score.to_node(self.ply(remaining));
+            table.store(key, Entry { score, ..entry })
+        }
+    }
+
+    /// Value of a leaf for the player to move.
+    pub fn evaluate(&self, state: &Configuration) -> Score {
+        Score::heuristic(self.evaluator.evaluate(state))
+    }
+
+    /// Value of a finished game with given remaining depth for the player to move.
+    pub fn final_score(&self, state: &Configuration, remaining: u8) -> Score {
+        Score::finished(-state.value(), self.ply(remaining))
+    }
+
+    /// Count a visited node.
+    pub fn node(&self) {
+        self.nodes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a beta cutoff.
+    pub fn cutoff(&self) {
+        self.cutoffs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Report of the search so far.
+    pub fn info(&self, depth: u8, score: Score, pv: Vec<Movement>) -> SearchInfo {
+        SearchInfo {
+            depth,
+            score,
+            nodes: self.nodes.load(Ordering::Relaxed),
+            cutoffs: self.cutoffs.load(Ordering::Relaxed),
+            elapsed: self.start.elapsed(),
+            pv,
+            exact: false,
+        }
+    }
+
+    /// Rebuild the principal variation of a search of given depth from its chosen movement.
+    /// Following moves come from the table and the last ply (which is never stored) is
+    /// replayed : it is the greedy choice.
+    pub fn principal_variation(
+        &self,
+        state: &Configuration,
+        movement: Option<Movement>,
+        depth: u8,
+    ) -> Vec<Movement> {
+        let mut pv = Vec::new();
+        let mut state = *state;
+        let mut next_move = movement;
+        let mut remaining = depth;
+        while let Some(movement) = next_move {
+            if remaining == 0 || !state.check_move(&movement) {
+                break;
+            }
+            pv.push(movement);
+            state.apply_movement(&movement);
+            remaining -= 1;
+            next_move = if remaining >= MIN_TABLE_DEPTH {
+                self.table
+                    .and_then(|table| table.peek(state.zobrist()))
+                    .and_then(|entry| entry.movement)
+            } else {
+                // first best move, as the search does
+                state
+                    .movements()
+                    .fold(None, |best, movement| {
+                        let value = -self.evaluate(&state.play(&movement));
+                        match best {
+                            Some((_, best_value)) if best_value >= value => best,
+                            _ => Some((movement, value)),
+                        }
+                    })
+                    .map(|(movement, _)| movement)
+            };
+        }
+        pv
+    }
+}
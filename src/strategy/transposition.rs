@@ -0,0 +1,339 @@
+//! Transposition table for the alpha - beta family of algorithms.
+//! Positions reached through different move orders are only searched once.
+//! The table has a fixed number of slots indexed by the zobrist hash of the configuration.
+//! Slots go by pairs : the first one keeps the deepest searches, the second one the latest.
+//! It is lock-free : each slot stores the key xored with the data so that a slot torn by
+//! concurrent writes from several rayon workers is detected and ignored.
+use super::score::Score;
+use crate::configuration::Movement;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Default number of slots (16 bytes each).
+const DEFAULT_SLOTS: usize = 1 << 20;
+/// Minimal remaining depth for which searches use the table.
+/// Nodes just above the leaves are too cheap to be worth a lookup.
+pub(crate) const MIN_TABLE_DEPTH: u8 = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// How a stored score relates to the real value of the position.
+pub enum Bound {
+    /// Score is the exact value.
+    Exact,
+    /// Real value is at least the score (we had a beta cutoff).
+    Lower,
+    /// Real value is at most the score (no move raised alpha).
+    Upper,
+}
+
+impl Bound {
+    /// Classify a score obtained with the given window.
+    pub fn classify(score: Score, alpha: Score, beta: Score) -> Self {
+        if score >= beta {
+            Bound::Lower
+        } else if score <= alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+/// Everything we remember about an already searched position.
+pub struct Entry {
+    /// Remaining depth the position was searched with.
+    pub depth: u8,
+    /// Kind of score.
+    pub bound: Bound,
+    /// Score for the player to move (finished games are counted from the stored position).
+    pub score: Score,
+    /// Best (or refutation) move found.
+    pub movement: Option<Movement>,
+}
+
+impl Entry {
+    /// Return stored score if it settles a search of given depth inside given window.
+    pub fn cutoff(&self, depth: u8, alpha: Score, beta: Score) -> Option<Score> {
+        if self.depth < depth {
+            return None;
+        }
+        match self.bound {
+            Bound::Exact => Some(self.score),
+            Bound::Lower if self.score >= beta => Some(self.score),
+            Bound::Upper if self.score <= alpha => Some(self.score),
+            _ => None,
+        }
+    }
+
+    /// Pack into 64 bits. Result is never 0 (empty slot marker).
+    fn pack(&self) -> u64 {
+        let movement = match self.movement {
+            None => 0,
+            Some(Movement::Duplicate(destination)) => 1 << 12 | u64::from(destination),
+            Some(Movement::Jump(source, destination)) => {
+                2 << 12 | u64::from(source) << 6 | u64::from(destination)
+            }
+        };
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        movement | u64::from(self.score.bits()) << 16 | u64::from(self.depth) << 32 | bound << 40
+    }
+
+    /// Unpack data packed with `pack`.
+    fn unpack(data: u64) -> Self {
+        let destination = (data & 0x3f) as u8;
+        let source = (data >> 6 & 0x3f) as u8;
+        let movement = match data >> 12 & 0xf {
+            1 => Some(Movement::Duplicate(destination)),
+            2 => Some(Movement::Jump(source, destination)),
+            _ => None,
+        };
+        let bound = match data >> 40 & 0x3 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        Entry {
+            depth: (data >> 32) as u8,
+            bound,
+            score: Score::from_bits((data >> 16) as u16),
+            movement,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    /// Return stored entry if it belongs to given key.
+    fn load(&self, key: u64) -> Option<Entry> {
+        let data = self.data.load(Ordering::Relaxed);
+        if data != 0 && self.check.load(Ordering::Relaxed) ^ data == key {
+            Some(Entry::unpack(data))
+        } else {
+            None
+        }
+    }
+
+    fn save(&self, key: u64, entry: Entry) {
+        let data = entry.pack();
+        self.check.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+/// Fixed size, thread safe transposition table.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::with_slots(DEFAULT_SLOTS)
+    }
+}
+
+impl fmt::Display for TranspositionTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} slots, {} hits, {} misses",
+            self.slots.len(),
+            self.hits(),
+            self.misses()
+        )
+    }
+}
+
+impl TranspositionTable {
+    /// New empty table with given number of slots (rounded up to a power of two).
+    pub fn with_slots(slots: usize) -> Self {
+        TranspositionTable {
+            slots: (0..slots.max(2).next_power_of_two())
+                .map(|_| Default::default())
+                .collect(),
+            hits: Default::default(),
+            misses: Default::default(),
+        }
+    }
+
+    /// The two slots where given key may be stored.
+    fn bucket(&self, key: u64) -> &[Slot] {
+        let index = key as usize & (self.slots.len() - 2);
+        &self.slots[index..index + 2]
+    }
+
+    /// Look for given zobrist key.
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let entry = self.peek(key);
+        if entry.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        entry
+    }
+
+    /// Look for given zobrist key without updating hit counters.
+    pub fn peek(&self, key: u64) -> Option<Entry> {
+        self.bucket(key).iter().find_map(|slot| slot.load(key))
+    }
+
+    /// Remember given entry for given zobrist key.
+    /// The first slot of the bucket is only replaced by searches at least as deep,
+    /// other entries go to the second slot.
+    /// An entry already stored for the same key is never replaced by a shallower one.
+    pub fn store(&self, key: u64, entry: Entry) {
+        let bucket = self.bucket(key);
+        let same_key = bucket
+            .iter()
+            .find_map(|slot| slot.load(key).map(|kept| (slot, kept)));
+        if let Some((slot, kept)) = same_key {
+            if kept.depth <= entry.depth {
+                slot.save(key, entry);
+            }
+            return;
+        }
+        let data = bucket[0].data.load(Ordering::Relaxed);
+        let deepest_kept = data != 0 && Entry::unpack(data).depth > entry.depth;
+        bucket[deepest_kept as usize].save(key, entry);
+    }
+
+    /// Forget all entries and reset counters.
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+
+    /// Number of successful probes.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Number of unsuccessful probes.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// Move given movement (if any) at the front of the movements to try.
+pub(crate) fn put_first(movements: &mut [Movement], movement: Option<Movement>) {
+    if let Some(movement) = movement {
+        if let Some(index) = movements.iter().position(|&m| m == movement) {
+            movements[..=index].rotate_right(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(depth: u8, bound: Bound, score: i8, movement: Option<Movement>) -> Entry {
+        Entry {
+            depth,
+            bound,
+            score: Score::heuristic(score),
+            movement,
+        }
+    }
+
+    fn same(a: Entry, b: Entry) -> bool {
+        a.depth == b.depth && a.bound == b.bound && a.score == b.score && a.movement == b.movement
+    }
+
+    #[test]
+    fn store_probe_round_trip() {
+        let table = TranspositionTable::with_slots(64);
+        let entries = [
+            entry(3, Bound::Exact, -12, Some(Movement::Duplicate(63))),
+            entry(7, Bound::Lower, 5, Some(Movement::Jump(0, 18))),
+            entry(2, Bound::Upper, 0, None),
+            Entry {
+                score: Score::finished(-4, 9),
+                ..entry(10, Bound::Exact, 0, Some(Movement::Jump(63, 45)))
+            },
+        ];
+        // one bucket per key
+        let key = |index: u64| (index << 40) | (index << 1);
+        for (index, &stored) in (1..).zip(&entries) {
+            table.store(key(index), stored);
+        }
+        for (index, &stored) in (1..).zip(&entries) {
+            let found = table.probe(key(index)).unwrap();
+            assert!(same(found, stored), "{:?} instead of {:?}", found, stored);
+        }
+        assert!(table.probe(12345).is_none());
+        assert_eq!((table.hits(), table.misses()), (4, 1));
+        table.clear();
+        assert!(table.peek(key(1)).is_none());
+    }
+
+    #[test]
+    fn shallower_stores_keep_deeper_entries() {
+        let table = TranspositionTable::with_slots(2);
+        let deep = entry(6, Bound::Exact, 3, Some(Movement::Duplicate(9)));
+        table.store(4, deep);
+        table.store(4, entry(2, Bound::Lower, -8, None));
+        assert!(same(table.peek(4).unwrap(), deep));
+        // other keys of the bucket do not evict it either
+        table.store(6, entry(1, Bound::Upper, 0, None));
+        assert!(same(table.peek(4).unwrap(), deep));
+        assert!(table.peek(6).is_some());
+        let deeper = entry(7, Bound::Upper, -1, Some(Movement::Jump(9, 11)));
+        table.store(4, deeper);
+        assert!(same(table.peek(4).unwrap(), deeper));
+        assert!(table.peek(6).is_some());
+    }
+
+    #[test]
+    fn cutoffs_respect_bounds_and_depth() {
+        let (alpha, beta) = (Score::heuristic(-5), Score::heuristic(5));
+        let exact = entry(4, Bound::Exact, 2, None);
+        assert_eq!(exact.cutoff(4, alpha, beta), Some(Score::heuristic(2)));
+        assert_eq!(exact.cutoff(5, alpha, beta), None);
+        assert!(entry(4, Bound::Lower, 7, None)
+            .cutoff(3, alpha, beta)
+            .is_some());
+        assert!(entry(4, Bound::Lower, 2, None)
+            .cutoff(3, alpha, beta)
+            .is_none());
+        assert!(entry(4, Bound::Upper, -7, None)
+            .cutoff(3, alpha, beta)
+            .is_some());
+        assert!(entry(4, Bound::Upper, 2, None)
+            .cutoff(3, alpha, beta)
+            .is_none());
+    }
+
+    #[test]
+    fn put_first_moves_to_front() {
+        let mut movements = [
+            Movement::Duplicate(1),
+            Movement::Duplicate(2),
+            Movement::Jump(3, 5),
+        ];
+        put_first(&mut movements, Some(Movement::Jump(3, 5)));
+        assert_eq!(movements[0], Movement::Jump(3, 5));
+        assert_eq!(
+            &movements[1..],
+            &[Movement::Duplicate(1), Movement::Duplicate(2)]
+        );
+        put_first(&mut movements, Some(Movement::Duplicate(60)));
+        assert_eq!(movements[0], Movement::Jump(3, 5));
+    }
+}
@@ -0,0 +1,58 @@
+//! Zobrist keys used to hash a `Configuration` into 64 bits.
+//! Each (cell, content) pair gets a random key and a configuration hashes to the xor of
+//! the keys of everything it contains. Keys are generated at compile time.
+use super::positions::Positions;
+
+/// Index of red blobs keys in `CELL_KEYS`.
+pub(crate) const RED: usize = 0;
+/// Index of blue blobs keys in `CELL_KEYS`.
+pub(crate) const BLUE: usize = 1;
+/// Index of holes keys in `CELL_KEYS`.
+pub(crate) const HOLE: usize = 2;
+
+/// Random keys for each cell content (red, blue, hole) and each cell.
+pub(crate) const CELL_KEYS: [[u64; 64]; 3] = generate_cell_keys();
+/// Key toggled when blue is the current player.
+pub(crate) const PLAYER_KEY: u64 = splitmix64(0x626c_6f62_7761_7221).1;
+
+/// One step of the splitmix64 generator. Return the new state and the generated number.
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_cell_keys() -> [[u64; 64]; 3] {
+    let mut keys = [[0; 64]; 3];
+    let mut state = 0x1234_5678_9abc_def0;
+    let mut content = 0;
+    while content < 3 {
+        let mut cell = 0;
+        while cell < 64 {
+            let (next_state, key) = splitmix64(state);
+            state = next_state;
+            keys[content][cell] = key;
+            cell += 1;
+        }
+        content += 1;
+    }
+    keys
+}
+
+/// Xor of the keys of all given `Positions` for given content.
+pub(crate) fn positions_key(positions: Positions, content: usize) -> u64 {
+    positions.positions().fold(0, |key, position| {
+        key ^ CELL_KEYS[content][position as usize]
+    })
+}
+
+/// Hash from scratch blobs, holes and current player.
+pub(crate) fn hash(blobs: &[Positions; 2], holes: Positions, current_player: bool) -> u64 {
+    let player_key = if current_player { PLAYER_KEY } else { 0 };
+    positions_key(blobs[0], RED)
+        ^ positions_key(blobs[1], BLUE)
+        ^ positions_key(holes, HOLE)
+        ^ player_key
+}
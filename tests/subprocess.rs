@@ -0,0 +1,63 @@
+//! Sub-process searches, with the helper binary built by cargo.
+use blobwar::board::Board;
+use blobwar::configuration::{Configuration, Movement};
+use blobwar::strategy::{IterativeDeepening, IterativeStrategy, Strategy};
+
+use std::fs;
+use std::process;
+use std::thread;
+
+const HELPER: &str = env!("CARGO_BIN_EXE_blobwar_iterative_deepening");
+
+#[test]
+fn concurrent_subprocess_searches() {
+    let board = Board::default();
+    let start = Configuration::new(&board);
+    let openings: Vec<Movement> = start.movements().take(6).collect();
+    thread::scope(|scope| {
+        for (index, opening) in openings.iter().enumerate() {
+            let state = start.play(opening);
+            scope.spawn(move || {
+                let algorithm = IterativeStrategy::from_index(index % 3).unwrap();
+                let mut strategy = IterativeDeepening::new(algorithm)
+                    .duration(300)
+                    .in_subprocess()
+                    .helper(HELPER);
+                for _ in 0..2 {
+                    let movement = strategy.compute_next_move(&state).unwrap();
+                    assert!(state.check_move(&movement));
+                    // no report means the greedy fallback played
+                    let info = strategy.search_info().expect("sub-process search failed");
+                    assert!(info.depth >= 1);
+                    assert_eq!(info.movement(), Some(movement));
+                }
+            });
+        }
+    });
+    // every segment was removed
+    let prefix = format!("blobwar-{}-", process::id());
+    if let Ok(entries) = fs::read_dir("/dev/shm") {
+        let left: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with(&prefix))
+            .collect();
+        assert!(left.is_empty(), "segments left: {:?}", left);
+    }
+}
+
+#[test]
+fn worker_searches() {
+    let board = Board::default();
+    let mut state = Configuration::new(&board);
+    let mut strategy = IterativeDeepening::new(IterativeStrategy::AlphaBeta)
+        .duration(100)
+        .in_worker()
+        .helper(HELPER);
+    for _ in 0..4 {
+        let movement = strategy.compute_next_move(&state).unwrap();
+        assert!(state.check_move(&movement));
+        assert!(strategy.search_info().is_some(), "worker search failed");
+        state.apply_movement(&movement);
+    }
+}